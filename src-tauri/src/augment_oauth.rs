@@ -3,6 +3,10 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use regex::Regex;
 
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::http_client::HostRateLimiter;
+use crate::logging;
+
 const CLIENT_ID: &str = "v";
 const AUTH_BASE_URL: &str = "https://auth.augmentcode.com";
 
@@ -48,73 +52,71 @@ pub struct CreditInfoResponse {
 }
 
 /// 从 auth session 中提取 access token
-pub async fn extract_token_from_session(session: &str) -> Result<AugmentTokenResponse, String> {
-    println!("=== 开始从 Session 提取 Token ===");
-    println!("Session (masked): {}...{}", &session[..10.min(session.len())], if session.len() > 10 { &session[session.len()-10..] } else { "" });
+pub async fn extract_token_from_session(session: &str) -> AppResult<AugmentTokenResponse> {
+    extract_token_with_limiter(session, None).await
+}
+
+/// 同 extract_token_from_session，limiter 不为空时每次请求前按主机限速
+pub async fn extract_token_with_limiter(
+    session: &str,
+    limiter: Option<&HostRateLimiter>,
+) -> AppResult<AugmentTokenResponse> {
+    log::info!(session = session; "开始从 Session 提取 Token");
 
     // 步骤1: 生成 PKCE 参数
-    println!("步骤1: 生成 PKCE 参数");
     let code_verifier = generate_random_string(32);
     let code_challenge = base64_url_encode(&sha256_hash(code_verifier.as_bytes()));
     let state = generate_random_string(42);
     let client_id = CLIENT_ID;
-    println!("  - code_verifier 长度: {}", code_verifier.len());
-    println!("  - code_challenge: {}", code_challenge);
-    println!("  - state 长度: {}", state.len());
 
     // 步骤2: 构建 terms-accept URL
-    println!("步骤2: 构建 terms-accept URL");
     let terms_url = format!(
         "{}/terms-accept?response_type=code&code_challenge={}&client_id={}&state={}&prompt=login",
         AUTH_BASE_URL, code_challenge, client_id, state
     );
-    println!("  - URL: {}", terms_url);
 
     // 步骤3: 使用 session cookie 访问 terms-accept 页面
-    println!("步骤3: 访问 terms-accept 页面");
     let client = crate::http_client::create_client()?;
+    throttle(limiter, &terms_url).await;
     let html_response = client
         .get(&terms_url)
         .header("Cookie", format!("session={}", session))
         .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
         .send()
         .await
-        .map_err(|e| format!("访问 terms-accept 页面失败: {}", e))?;
-
-    println!("  - HTTP 状态码: {}", html_response.status());
+        .map_err(|e| AppError::network("访问 terms-accept 页面失败", e))?;
 
+    let html_status = html_response.status();
+    let final_url = html_response.url().clone();
     let html = html_response.text().await
-        .map_err(|e| format!("读取 HTML 响应失败: {}", e))?;
-    
-    println!("  - HTML 长度: {} 字符", html.len());
+        .map_err(|e| AppError::network("读取 HTML 响应失败", e))?;
 
-    // 步骤4: 从 HTML 中提取授权码、state 和 tenant_url
-    println!("步骤4: 从 HTML 提取授权码和 tenant_url");
+    log::debug!(status = html_status.as_u16(), final_url = final_url.as_str(), html_len = html.len(); "terms-accept 页面已返回");
+
+    // 步骤4: 从 HTML 中提取授权码、state 和 tenant_url；没有授权码时判断失败原因
     let code_regex = Regex::new(r#"code:\s*"([^"]+)""#).unwrap();
     let state_regex = Regex::new(r#"state:\s*"([^"]+)""#).unwrap();
     let tenant_url_regex = Regex::new(r#"tenant_url:\s*"([^"]+)""#).unwrap();
 
     let code = code_regex.captures(&html)
+        .filter(|_| html_status.is_success())
         .and_then(|cap| cap.get(1))
         .map(|m| m.as_str())
-        .ok_or("SESSION_ERROR_OR_ACCOUNT_BANNED: 无法提取授权码")?;
+        .ok_or_else(|| classify_terms_page(html_status, &final_url, &html))?;
 
     let parsed_state = state_regex.captures(&html)
         .and_then(|cap| cap.get(1))
         .map(|m| m.as_str())
-        .ok_or("SESSION_ERROR_OR_ACCOUNT_BANNED: 无法提取 state")?;
+        .ok_or_else(|| unexpected_page("无法提取 state"))?;
 
     let tenant_url = tenant_url_regex.captures(&html)
         .and_then(|cap| cap.get(1))
         .map(|m| m.as_str())
-        .ok_or("SESSION_ERROR_OR_ACCOUNT_BANNED: 无法提取 tenant_url")?;
+        .ok_or_else(|| unexpected_page("无法提取 tenant_url"))?;
 
-    println!("  - 授权码 (masked): {}...", &code[..10.min(code.len())]);
-    println!("  - State (masked): {}...", &parsed_state[..10.min(parsed_state.len())]);
-    println!("  - Tenant URL: {}", tenant_url);
+    log::debug!(code = code, state = parsed_state, tenant_url = tenant_url; "已提取授权码");
 
     // 步骤5: 使用授权码交换 access token
-    println!("步骤5: 交换 access token");
     let token_url = format!("{}token", tenant_url);
     let token_payload = serde_json::json!({
         "grant_type": "authorization_code",
@@ -124,65 +126,72 @@ pub async fn extract_token_from_session(session: &str) -> Result<AugmentTokenRes
         "code": code
     });
 
-    println!("  - Token URL: {}", token_url);
-
+    throttle(limiter, &token_url).await;
     let token_response = client
         .post(&token_url)
         .header("Content-Type", "application/json")
         .json(&token_payload)
         .send()
         .await
-        .map_err(|e| format!("交换 token 失败: {}", e))?;
+        .map_err(|e| AppError::network("交换 token 失败", e))?;
 
-    println!("  - HTTP 状态码: {}", token_response.status());
+    let token_status = token_response.status();
+    log::debug!(status = token_status.as_u16(); "token 交换已返回");
 
-    let token_data: TokenApiResponse = token_response.json().await
-        .map_err(|e| format!("解析 token 响应失败: {}", e))?;
+    // 先检查状态码，避免把服务端的错误页面当作 JSON 解析而掩盖真实原因
+    let token_body = token_response.text().await
+        .map_err(|e| AppError::network("读取 token 响应失败", e))?;
+    if !token_status.is_success() {
+        return Err(AppError::new(ErrorCode::from_status(token_status), format!("交换 token 失败 ({})", token_status))
+            .with_details(logging::truncate(&logging::redact(&token_body), 300)));
+    }
 
-    println!("  - Access token (masked): {}...", &token_data.access_token[..20.min(token_data.access_token.len())]);
+    let token_data: TokenApiResponse = serde_json::from_str(&token_body)
+        .map_err(|e| AppError::new(ErrorCode::UnexpectedResponse, "token 响应格式不符合预期")
+            .with_details(format!("{}: {}", e, logging::truncate(&logging::redact(&token_body), 300))))?;
 
     // 步骤6: 并行获取用户邮箱和积分信息
-    println!("步骤6: 并行获取用户信息和积分信息");
     let token = token_data.access_token.clone();
     let tenant_url_clone = tenant_url.to_string();
 
     let (email_result, credit_result) = tokio::join!(
-        get_models(&token, &tenant_url_clone),
-        get_credit_info(&token, &tenant_url_clone)
+        async {
+            throttle(limiter, &tenant_url_clone).await;
+            get_models(&token, &tenant_url_clone).await
+        },
+        async {
+            throttle(limiter, &tenant_url_clone).await;
+            get_credit_info(&token, &tenant_url_clone).await
+        }
     );
 
     // 步骤7: 处理邮箱结果
-    println!("步骤7: 处理用户邮箱");
     let email = match email_result {
-        Ok(models_response) => {
-            println!("  - 邮箱: {}", models_response.user.email);
-            Some(models_response.user.email)
-        },
+        Ok(models_response) => Some(models_response.user.email),
         Err(err) => {
-            println!("  - 获取邮箱失败: {}", err);
+            log::warn!("获取邮箱失败: {}", err);
             None
         }
     };
 
     // 步骤8: 处理积分信息结果
-    println!("步骤8: 处理积分信息");
     let (credits_balance, expiry_date) = match credit_result {
-        Ok(credit_info) => {
-            let balance = credit_info.usage_units_remaining.floor() as i32;
-            println!("  - 积分余额: {}", balance);
-            println!("  - 过期时间: {}", credit_info.current_billing_cycle_end_date_iso);
-            (
-                Some(balance),
-                Some(credit_info.current_billing_cycle_end_date_iso),
-            )
-        },
+        Ok(credit_info) => (
+            Some(credit_info.usage_units_remaining.floor() as i32),
+            Some(credit_info.current_billing_cycle_end_date_iso),
+        ),
         Err(err) => {
-            println!("  - 获取积分信息失败: {}", err);
+            log::warn!("获取积分信息失败: {}", err);
             (None, None)
         }
     };
 
-    println!("=== Token 提取成功 ===");
+    log::info!(
+        access_token = token_data.access_token.as_str(),
+        email = email.as_deref().unwrap_or(""),
+        credits_balance = credits_balance.unwrap_or_default();
+        "Token 提取成功"
+    );
 
     // 步骤9: 返回完整的 token 响应
     Ok(AugmentTokenResponse {
@@ -195,7 +204,7 @@ pub async fn extract_token_from_session(session: &str) -> Result<AugmentTokenRes
 }
 
 /// 获取用户邮箱
-pub async fn get_models(token: &str, tenant_url: &str) -> Result<ModelsResponse, String> {
+pub async fn get_models(token: &str, tenant_url: &str) -> AppResult<ModelsResponse> {
     let client = crate::http_client::create_client()?;
     let base_url = if tenant_url.ends_with('/') {
         tenant_url.to_string()
@@ -212,22 +221,24 @@ pub async fn get_models(token: &str, tenant_url: &str) -> Result<ModelsResponse,
         .json(&serde_json::json!({}))
         .send()
         .await
-        .map_err(|e| format!("HTTP 请求失败: {}", e))?;
+        .map_err(|e| AppError::network("HTTP 请求失败", e))?;
 
-    if !response.status().is_success() {
+    let status = response.status();
+    if !status.is_success() {
         let error_body = response.text().await
             .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("API 请求失败: {}", error_body));
+        return Err(AppError::new(ErrorCode::from_status(status), format!("API 请求失败 ({})", status))
+            .with_details(logging::truncate(&logging::redact(&error_body), 300)));
     }
 
     let models_info: ModelsResponse = response.json().await
-        .map_err(|e| format!("解析响应失败: {}", e))?;
+        .map_err(|e| AppError::parse("解析响应失败", e))?;
 
     Ok(models_info)
 }
 
 /// 获取积分余额
-pub async fn get_credit_info(token: &str, tenant_url: &str) -> Result<CreditInfoResponse, String> {
+pub async fn get_credit_info(token: &str, tenant_url: &str) -> AppResult<CreditInfoResponse> {
     let client = crate::http_client::create_client()?;
     let base_url = if tenant_url.ends_with('/') {
         tenant_url.to_string()
@@ -244,21 +255,106 @@ pub async fn get_credit_info(token: &str, tenant_url: &str) -> Result<CreditInfo
         .json(&serde_json::json!({}))
         .send()
         .await
-        .map_err(|e| format!("HTTP 请求失败: {}", e))?;
+        .map_err(|e| AppError::network("HTTP 请求失败", e))?;
 
-    if !response.status().is_success() {
+    let status = response.status();
+    if !status.is_success() {
         let error_body = response.text().await
             .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("API 请求失败: {}", error_body));
+        return Err(AppError::new(ErrorCode::from_status(status), format!("API 请求失败 ({})", status))
+            .with_details(logging::truncate(&logging::redact(&error_body), 300)));
     }
 
     let credit_info: CreditInfoResponse = response.json().await
-        .map_err(|e| format!("解析响应失败: {}", e))?;
+        .map_err(|e| AppError::parse("解析响应失败", e))?;
 
     Ok(credit_info)
 }
 
 // 辅助函数
+
+async fn throttle(limiter: Option<&HostRateLimiter>, url: &str) {
+    if let Some(limiter) = limiter {
+        limiter.acquire(url).await;
+    }
+}
+
+/// 页面标题、主标题和提示框中表示账号被暂停的文字
+const SUSPENDED_MARKERS: &[&str] = &["account suspended", "account has been suspended", "account is suspended"];
+
+/// 页面标题、主标题和提示框中表示账号被封禁的文字
+const BANNED_MARKERS: &[&str] = &[
+    "account banned",
+    "account has been banned",
+    "account is banned",
+    "account has been disabled",
+    "account is disabled",
+];
+
+/// 页面标题、主标题和提示框中表示需要接受服务条款的文字
+const TERMS_MARKERS: &[&str] = &["accept the terms", "terms of service", "terms of use", "terms and conditions"];
+
+/// 提取页面标题（title、h1、h2）和提示框（role="alert"）中的文字，统一转为小写
+/// 只在这些位置匹配关键字，避免页脚链接、说明文字中的 blocked、suspension 等词导致误判
+fn page_headlines(html: &str) -> String {
+    let headline_regex = Regex::new(r"(?is)<(?:title|h1|h2)\b[^>]*>(.*?)</(?:title|h1|h2)\s*>").unwrap();
+    let alert_regex = Regex::new(r#"(?is)<[a-z0-9]+\b[^>]*\brole\s*=\s*["']alert["'][^>]*>(.*?)</[a-z0-9]+\s*>"#).unwrap();
+    let tag_regex = Regex::new(r"<[^>]*>").unwrap();
+    let space_regex = Regex::new(r"\s+").unwrap();
+
+    headline_regex.captures_iter(html)
+        .chain(alert_regex.captures_iter(html))
+        .filter_map(|cap| cap.get(1))
+        .map(|inner| {
+            let text = tag_regex.replace_all(inner.as_str(), " ");
+            space_regex.replace_all(text.trim(), " ").to_lowercase()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 页面中有提交到 terms-accept 的表单，说明停在了接受服务条款的步骤
+fn has_terms_form(html: &str) -> bool {
+    Regex::new(r#"(?is)<form\b[^>]*\baction\s*=\s*["'][^"']*terms-accept"#).unwrap().is_match(html)
+}
+
+/// terms-accept 页面没有返回授权码时判断失败原因
+/// 依次检查：限流和服务端错误、跳转到登录页（Session 过期）、暂停或封禁提示、未接受服务条款
+fn classify_terms_page(status: reqwest::StatusCode, final_url: &reqwest::Url, html: &str) -> AppError {
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        return AppError::http_status("访问 terms-accept 页面失败", status);
+    }
+
+    let redirected_to_login = final_url.path().contains("login") && !final_url.path().contains("terms-accept");
+    if redirected_to_login || status == reqwest::StatusCode::UNAUTHORIZED {
+        return AppError::new(ErrorCode::SessionInvalid, "Session 已过期，需要重新登录")
+            .with_details(format!("{} {}", status, final_url.path()));
+    }
+
+    let headlines = page_headlines(html);
+    let contains_any = |markers: &[&str]| markers.iter().any(|marker| headlines.contains(marker));
+
+    if contains_any(SUSPENDED_MARKERS) {
+        return AppError::new(ErrorCode::AccountSuspended, "账号已被暂停");
+    }
+    if contains_any(BANNED_MARKERS) {
+        return AppError::new(ErrorCode::AccountBanned, "账号已被封禁");
+    }
+    if has_terms_form(html) || contains_any(TERMS_MARKERS) {
+        return AppError::new(ErrorCode::TermsNotAccepted, "账号尚未接受服务条款，请先在浏览器中登录并接受");
+    }
+    if !status.is_success() {
+        return AppError::http_status("访问 terms-accept 页面失败", status);
+    }
+
+    unexpected_page("无法提取授权码")
+}
+
+/// 页面结构与预期不符，通常是登录流程有变化
+fn unexpected_page(details: &str) -> AppError {
+    AppError::new(ErrorCode::UnexpectedResponse, "terms-accept 页面结构不符合预期").with_details(details)
+}
+
 fn generate_random_string(length: usize) -> String {
     use rand::RngCore;
     let mut rng = rand::thread_rng();
@@ -277,3 +373,67 @@ fn sha256_hash(data: &[u8]) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{StatusCode, Url};
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/terms_accept/", $name))
+        };
+    }
+
+    fn terms_url() -> Url {
+        Url::parse(&format!("{}/terms-accept?response_type=code", AUTH_BASE_URL)).unwrap()
+    }
+
+    fn classify(status: u16, html: &str) -> ErrorCode {
+        classify_terms_page(StatusCode::from_u16(status).unwrap(), &terms_url(), html).code
+    }
+
+    #[test]
+    fn login_redirect_means_session_expired() {
+        let login_url = Url::parse(&format!("{}/login?state=abc", AUTH_BASE_URL)).unwrap();
+        let error = classify_terms_page(StatusCode::OK, &login_url, fixture!("login.html"));
+        assert_eq!(error.code, ErrorCode::SessionInvalid);
+        assert_eq!(classify(401, ""), ErrorCode::SessionInvalid);
+    }
+
+    #[test]
+    fn banned_page_ignores_footer_wording() {
+        assert_eq!(classify(200, fixture!("banned.html")), ErrorCode::AccountBanned);
+        assert_eq!(classify(403, fixture!("banned.html")), ErrorCode::AccountBanned);
+    }
+
+    #[test]
+    fn suspended_notice_is_detected() {
+        assert_eq!(classify(200, fixture!("suspended.html")), ErrorCode::AccountSuspended);
+    }
+
+    #[test]
+    fn terms_form_is_detected() {
+        assert_eq!(classify(200, fixture!("terms.html")), ErrorCode::TermsNotAccepted);
+    }
+
+    #[test]
+    fn rate_limit_and_server_errors_use_status() {
+        assert_eq!(classify(429, fixture!("rate_limited.html")), ErrorCode::RateLimited);
+        assert_eq!(classify(502, fixture!("server_error.html")), ErrorCode::ServerError);
+        // 错误页面中即使出现封禁字样也按状态码处理
+        assert_eq!(classify(503, fixture!("banned.html")), ErrorCode::ServerError);
+    }
+
+    #[test]
+    fn unknown_layout_is_unexpected() {
+        assert_eq!(classify(200, fixture!("unexpected.html")), ErrorCode::UnexpectedResponse);
+        assert_eq!(classify(404, fixture!("unexpected.html")), ErrorCode::HttpStatus);
+    }
+
+    #[test]
+    fn headlines_are_normalized() {
+        let html = "<title>\n  Account\n  <b>Suspended</b> </title><h1 class=\"x\">Hi</h1><p>body</p>";
+        assert_eq!(page_headlines(html), "account suspended\nhi");
+    }
+}
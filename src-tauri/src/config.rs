@@ -1,72 +1,139 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
-use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+use tauri::State;
+
+use crate::error::{AppError, AppResult};
+use crate::health_check::HealthCheckConfig;
+use crate::remote_schedule::RemoteSource;
+use crate::storage;
+use crate::token_query::SavedFilter;
+use crate::token_store::TokenStore;
+
+/// Token 存储后端
+/// 切换后首次读取时自动迁移数据，原来的文件改名为 tokens.json.migrated 或 tokens.db.migrated
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// tokens.json（支持备份与保险库加密）
+    #[default]
+    Json,
+    /// tokens.db（SQLite，适合大量记录）
+    Sqlite,
+}
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct AppConfig {
     pub url: String,
     pub file_path: String,
+    #[serde(default)]
+    pub storage_backend: StorageBackend,
+    /// 命名的过滤条件
+    #[serde(default)]
+    pub saved_filters: Vec<SavedFilter>,
+    /// 回收站记录保留天数，未设置时为 30 天，0 表示永不自动清理
+    #[serde(default)]
+    pub trash_retention_days: Option<u32>,
+    /// 定时导入的远端数据源
+    #[serde(default)]
+    pub remote_sources: Vec<RemoteSource>,
+    /// 后台定时检查账号
+    #[serde(default)]
+    pub health_check: HealthCheckConfig,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            url: String::new(),
-            file_path: String::new(),
-        }
-    }
+/// 已加载的配置及加载时文件的修改时间
+struct CachedConfig {
+    modified: Option<SystemTime>,
+    config: AppConfig,
 }
 
-/// 获取配置文件路径
-fn get_config_path() -> Result<PathBuf, String> {
-    // 获取 APPDATA 环境变量
-    let app_data = env::var("APPDATA")
-        .or_else(|_| env::var("HOME").map(|home| format!("{}/.config", home)))
-        .map_err(|e| format!("获取应用数据目录失败: {}", e))?;
-
-    // 构建配置目录路径: %APPDATA%\com.cubezhao.aug-session-sync\
-    let config_dir = PathBuf::from(app_data).join("com.cubezhao.aug-session-sync");
-
-    // 确保目录存在
-    if !config_dir.exists() {
-        fs::create_dir_all(&config_dir)
-            .map_err(|e| format!("创建应用数据目录失败: {}", e))?;
-    }
+/// 配置缓存；所有读写经过同一把锁，避免并发的读-改-写互相覆盖
+/// 文件被外部修改（修改时间变化）时重新读取
+static CONFIG: Mutex<Option<CachedConfig>> = Mutex::new(None);
 
-    Ok(config_dir.join("config.json"))
+/// 获取配置文件路径
+/// 路径: <应用数据目录>/config.json
+fn get_config_path() -> AppResult<PathBuf> {
+    Ok(crate::paths::data_dir()?.join("config.json"))
 }
 
-/// 加载配置
-#[tauri::command]
-pub fn load_config() -> Result<AppConfig, String> {
-    let config_path = get_config_path()?;
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
 
-    if !config_path.exists() {
-        // 配置文件不存在，返回默认配置
-        return Ok(AppConfig::default());
+/// 在锁内返回最新的配置
+fn cached<'a>(cache: &'a mut Option<CachedConfig>, config_path: &Path) -> AppResult<&'a mut CachedConfig> {
+    let modified = modified_time(config_path);
+    let stale = cache.as_ref().is_none_or(|cached| cached.modified != modified);
+
+    if stale {
+        let config = if config_path.exists() {
+            let content = fs::read_to_string(config_path)
+                .map_err(|e| AppError::io("读取配置文件失败", e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| AppError::parse("解析配置文件失败", e))?
+        } else {
+            // 配置文件不存在，使用默认配置
+            AppConfig::default()
+        };
+        *cache = Some(CachedConfig { modified, config });
     }
 
-    let content = fs::read_to_string(&config_path)
-        .map_err(|e| format!("读取配置文件失败: {}", e))?;
+    Ok(cache.as_mut().unwrap())
+}
+
+fn write_config(cache: &mut Option<CachedConfig>, config_path: &Path, config: AppConfig) -> AppResult<()> {
+    let content = serde_json::to_string_pretty(&config)
+        .map_err(|e| AppError::parse("序列化配置失败", e))?;
+    storage::atomic_write(config_path, content.as_bytes())
+        .map_err(|e| AppError::io("写入配置文件失败", e))?;
 
-    let config: AppConfig = serde_json::from_str(&content)
-        .map_err(|e| format!("解析配置文件失败: {}", e))?;
+    *cache = Some(CachedConfig { modified: modified_time(config_path), config });
+    Ok(())
+}
 
-    Ok(config)
+fn lock_config() -> MutexGuard<'static, Option<CachedConfig>> {
+    CONFIG.lock().unwrap_or_else(|e| e.into_inner())
 }
 
-/// 保存配置
+/// 加载配置
 #[tauri::command]
-pub fn save_config(config: AppConfig) -> Result<(), String> {
+pub fn load_config() -> AppResult<AppConfig> {
     let config_path = get_config_path()?;
+    let mut cache = lock_config();
+    Ok(cached(&mut cache, &config_path)?.config.clone())
+}
 
-    let content = serde_json::to_string_pretty(&config)
-        .map_err(|e| format!("序列化配置失败: {}", e))?;
+/// 保存配置，存储后端变化时通知 TokenStore 切换
+/// 保险库已启用时拒绝切换到 SQLite 存储（tokens.db 不支持加密）
+#[tauri::command]
+pub async fn save_config(store: State<'_, TokenStore>, config: AppConfig) -> AppResult<()> {
+    if config.storage_backend == StorageBackend::Sqlite && crate::vault::is_enabled()? {
+        return Err(AppError::invalid_state("已启用保险库，SQLite 存储不支持加密，请改回 JSON 存储"));
+    }
 
-    fs::write(&config_path, content)
-        .map_err(|e| format!("写入配置文件失败: {}", e))?;
+    let backend = config.storage_backend;
+    {
+        let config_path = get_config_path()?;
+        let mut cache = lock_config();
+        write_config(&mut cache, &config_path, config)?;
+    }
+    store.switch_backend(backend).await;
 
     Ok(())
 }
 
+/// 在锁内读取、修改并保存配置；f 返回错误时不写入
+pub fn update_config<T>(f: impl FnOnce(&mut AppConfig) -> AppResult<T>) -> AppResult<T> {
+    let config_path = get_config_path()?;
+    let mut cache = lock_config();
+
+    let mut config = cached(&mut cache, &config_path)?.config.clone();
+    let result = f(&mut config)?;
+    write_config(&mut cache, &config_path, config)?;
+
+    Ok(result)
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 稳定的错误码，前端根据 code 判断错误类型，不再匹配错误文本
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// 网络错误（连接失败、超时等）
+    Network,
+    /// 服务端返回非成功的 HTTP 状态码
+    HttpStatus,
+    /// 请求被限流（HTTP 429）
+    RateLimited,
+    /// 服务端错误（HTTP 5xx）
+    ServerError,
+    /// Session 无效或已过期，需要重新登录
+    SessionInvalid,
+    /// 账号被封禁
+    AccountBanned,
+    /// 账号被暂停
+    AccountSuspended,
+    /// 账号尚未接受服务条款
+    TermsNotAccepted,
+    /// 响应内容不符合预期（页面结构变化等）
+    UnexpectedResponse,
+    /// 数据解析失败
+    Parse,
+    /// 文件读写失败
+    Io,
+    /// 数据库操作失败
+    Database,
+    /// 记录不存在
+    NotFound,
+    /// 记录重复
+    Duplicate,
+    /// 参数无效
+    InvalidInput,
+    /// 当前状态不允许该操作
+    InvalidState,
+    /// 保险库已锁定
+    VaultLocked,
+    /// 保险库口令错误
+    VaultBadPassphrase,
+    /// 加解密失败
+    Crypto,
+    /// 其他内部错误
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn from_status(status: reqwest::StatusCode) -> Self {
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            Self::RateLimited
+        } else if status.is_server_error() {
+            Self::ServerError
+        } else {
+            Self::HttpStatus
+        }
+    }
+}
+
+/// 所有命令统一返回的错误类型
+/// 序列化为 `{ code, message, details }`，message 面向用户，details 保存底层错误信息
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: impl fmt::Display) -> Self {
+        self.details = Some(details.to_string());
+        self
+    }
+
+    pub fn io(message: impl Into<String>, source: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::Io, message).with_details(source)
+    }
+
+    pub fn parse(message: impl Into<String>, source: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::Parse, message).with_details(source)
+    }
+
+    pub fn database(message: impl Into<String>, source: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::Database, message).with_details(source)
+    }
+
+    /// 网络请求失败；如果错误带有 HTTP 状态码则按状态码归类
+    pub fn network(message: impl Into<String>, source: reqwest::Error) -> Self {
+        let code = match source.status() {
+            Some(status) => ErrorCode::from_status(status),
+            None => ErrorCode::Network,
+        };
+        let message = if source.is_timeout() {
+            format!("{}（请求超时）", message.into())
+        } else {
+            message.into()
+        };
+        Self::new(code, message).with_details(source)
+    }
+
+    /// 非成功的 HTTP 状态码：429 归为 RATE_LIMITED，5xx 归为 SERVER_ERROR，其余为 HTTP_STATUS
+    pub fn http_status(message: impl Into<String>, status: reqwest::StatusCode) -> Self {
+        Self::new(ErrorCode::from_status(status), message).with_details(status)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn duplicate(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Duplicate, message)
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidInput, message)
+    }
+
+    pub fn invalid_state(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidState, message)
+    }
+
+    pub fn vault_locked() -> Self {
+        Self::new(ErrorCode::VaultLocked, "保险库已锁定，请先解锁")
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.details {
+            Some(details) => write!(f, "{}: {}", self.message, details),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// 在阻塞线程池中执行大文件读写、密钥派生等耗时操作，避免占用异步运行时的工作线程
+pub async fn spawn_blocking<T, F>(f: F) -> AppResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> AppResult<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AppError::new(ErrorCode::Internal, "后台任务异常退出").with_details(e))?
+}
@@ -0,0 +1,240 @@
+use chrono::{Local, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::{AppResult, ErrorCode};
+use crate::http_client::HostRateLimiter;
+use crate::lifecycle;
+use crate::token_manager::TokenRecord;
+use crate::token_manager::parse_timestamp;
+use crate::token_store::TokenStore;
+
+/// 检查是否有到期记录的间隔
+const TICK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 每轮检查完成后发出的事件
+pub const HEALTH_CHECK_EVENT: &str = "health-check";
+
+/// 后台定时检查账号的配置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthCheckConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 同一账号两次检查的最小间隔（分钟）
+    #[serde(default = "default_interval_minutes")]
+    pub interval_minutes: u32,
+    /// 相邻两个账号之间的等待时间（秒），避免短时间内大量请求
+    #[serde(default = "default_delay_seconds")]
+    pub delay_seconds: u64,
+    /// 免打扰时段（本地时间），期间不发起检查
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+fn default_interval_minutes() -> u32 {
+    360
+}
+
+fn default_delay_seconds() -> u64 {
+    2
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: default_interval_minutes(),
+            delay_seconds: default_delay_seconds(),
+            quiet_hours: None,
+        }
+    }
+}
+
+/// 免打扰时段，格式为 HH:MM；start 晚于 end 时表示跨越午夜，例如 23:00 - 07:00
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+impl QuietHours {
+    /// 时间格式无效时视为没有免打扰时段
+    fn contains(&self, time: NaiveTime) -> bool {
+        let (Ok(start), Ok(end)) = (
+            NaiveTime::parse_from_str(self.start.trim(), "%H:%M"),
+            NaiveTime::parse_from_str(self.end.trim(), "%H:%M"),
+        ) else {
+            log::warn!(start = self.start.as_str(), end = self.end.as_str(); "免打扰时段格式无效");
+            return false;
+        };
+
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
+impl HealthCheckConfig {
+    fn is_quiet_now(&self) -> bool {
+        self.quiet_hours.as_ref().is_some_and(|quiet| quiet.contains(Local::now().time()))
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct HealthCheckEvent {
+    pub checked: usize,
+    /// 检查失败（包括 Session 失效、封禁和网络错误）的记录数
+    pub failed: usize,
+}
+
+/// 未跳过检查且距上次检查已超过间隔的记录
+fn is_due(token: &TokenRecord, interval_minutes: u32) -> bool {
+    if token.skip_check {
+        return false;
+    }
+
+    let checked_at = token.last_check.as_ref().and_then(|check| parse_timestamp(&check.checked_at));
+    match checked_at {
+        Some(checked_at) => Utc::now() - checked_at >= chrono::Duration::minutes(interval_minutes.into()),
+        None => true,
+    }
+}
+
+/// 依次检查到期的记录；配置关闭或进入免打扰时段时提前结束，剩余记录留到下一轮
+async fn tick(app: &AppHandle) -> AppResult<()> {
+    let config = crate::config::load_config()?.health_check;
+    if !config.enabled || config.is_quiet_now() {
+        return Ok(());
+    }
+
+    let store = app.state::<TokenStore>();
+    let limiter = app.state::<HostRateLimiter>();
+    let due: Vec<String> = match store.list().await {
+        Ok(tokens) => tokens.into_iter()
+            .filter(|token| is_due(token, config.interval_minutes))
+            .map(|token| token.id)
+            .collect(),
+        // 保险库锁定时跳过，解锁后再检查
+        Err(e) if e.code == ErrorCode::VaultLocked => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    log::info!(count = due.len(); "开始定时检查账号");
+
+    let mut checked = 0;
+    let mut failed = 0;
+    for (index, id) in due.iter().enumerate() {
+        if index > 0 {
+            tokio::time::sleep(Duration::from_secs(config.delay_seconds)).await;
+
+            let config = crate::config::load_config()?.health_check;
+            if !config.enabled || config.is_quiet_now() {
+                log::info!(checked = checked; "定时检查已暂停，剩余账号留到下一轮");
+                break;
+            }
+        }
+
+        match lifecycle::check_record(&store, id, &limiter).await {
+            Ok(_) => {}
+            // 检查期间被删除或 Session 被修改的记录不计入结果，下一轮再检查
+            Err(e) if matches!(e.code, ErrorCode::NotFound | ErrorCode::InvalidState) => continue,
+            Err(e) if e.code == ErrorCode::VaultLocked => break,
+            // 被限流时结束本轮，剩余账号留到下一轮，避免继续加重限流
+            Err(e) if e.code == ErrorCode::RateLimited => {
+                log::warn!(checked = checked; "定时检查被限流，剩余账号留到下一轮");
+                failed += 1;
+                checked += 1;
+                break;
+            }
+            Err(e) => {
+                log::warn!(id = id.as_str(); "定时检查失败: {}", e);
+                failed += 1;
+            }
+        }
+        checked += 1;
+    }
+
+    log::info!(checked = checked, failed = failed; "定时检查完成");
+    if checked > 0 {
+        if let Err(e) = app.emit(HEALTH_CHECK_EVENT, HealthCheckEvent { checked, failed }) {
+            log::warn!("发送检查事件失败: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// 启动后台检查任务，每分钟检查一次是否有到期的账号
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Err(e) = tick(&app).await {
+                log::warn!("定时检查账号失败: {}", e);
+            }
+            tokio::time::sleep(TICK_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet(start: &str, end: &str) -> QuietHours {
+        QuietHours { start: start.to_string(), end: end.to_string() }
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn quiet_hours_within_one_day() {
+        let hours = quiet("09:00", "17:30");
+        assert!(hours.contains(at("09:00")));
+        assert!(hours.contains(at("17:29")));
+        assert!(!hours.contains(at("17:30")));
+        assert!(!hours.contains(at("08:59")));
+    }
+
+    #[test]
+    fn quiet_hours_crossing_midnight() {
+        let hours = quiet("23:00", "07:00");
+        assert!(hours.contains(at("23:00")));
+        assert!(hours.contains(at("00:00")));
+        assert!(hours.contains(at("06:59")));
+        assert!(!hours.contains(at("07:00")));
+        assert!(!hours.contains(at("12:00")));
+        assert!(!hours.contains(at("22:59")));
+    }
+
+    #[test]
+    fn invalid_quiet_hours_are_ignored() {
+        assert!(!quiet("25:00", "07:00").contains(at("03:00")));
+        assert!(!quiet("23:00", "").contains(at("23:30")));
+        // 首尾空白可以接受
+        assert!(quiet(" 23:00 ", "07:00 ").contains(at("23:30")));
+    }
+
+    #[test]
+    fn skipped_and_recently_checked_records_are_not_due() {
+        let mut token = TokenRecord::test("a", "session-a");
+        assert!(is_due(&token, 60));
+
+        token.last_check = Some(crate::lifecycle::CheckResult {
+            checked_at: (Utc::now() - chrono::Duration::minutes(30)).to_rfc3339(),
+            outcome: crate::lifecycle::CheckOutcome::Ok,
+            error: None,
+        });
+        assert!(!is_due(&token, 60));
+        assert!(is_due(&token, 30));
+
+        token.skip_check = true;
+        assert!(!is_due(&token, 0));
+    }
+}
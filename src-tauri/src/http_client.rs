@@ -1,21 +1,69 @@
-use reqwest::Client;
+use reqwest::{Client, Url};
+use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::error::{AppError, AppResult, ErrorCode};
 
 /// 创建HTTP客户端（支持 cookies）
-pub fn create_client() -> Result<Client, String> {
+pub fn create_client() -> AppResult<Client> {
     let client = Client::builder()
         .timeout(Duration::from_secs(30))
         .connect_timeout(Duration::from_secs(10))
         .cookie_store(true)  // 启用 cookie 存储
         .build()
-        .map_err(|e| format!("创建HTTP客户端失败: {}", e))?;
+        .map_err(|e| AppError::new(ErrorCode::Internal, "创建HTTP客户端失败").with_details(e))?;
 
     Ok(client)
 }
 
+/// 默认每个主机每秒的请求数
+const DEFAULT_REQUESTS_PER_SECOND: u32 = 2;
+
+/// 按主机限制请求频率：同一主机的相邻两次请求至少间隔 min_interval
+/// 作为全局状态注册，批量刷新和后台检查共用同一个实例
+pub struct HostRateLimiter {
+    min_interval: Duration,
+    /// 主机 -> 下一个可用的请求时间
+    next_slot: Mutex<HashMap<String, Instant>>,
+}
+
+impl Default for HostRateLimiter {
+    fn default() -> Self {
+        Self::per_second(DEFAULT_REQUESTS_PER_SECOND)
+    }
+}
+
+impl HostRateLimiter {
+    pub fn per_second(requests: u32) -> Self {
+        Self {
+            min_interval: Duration::from_secs(1) / requests.max(1),
+            next_slot: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 等待到该 URL 所在主机的下一个请求时间
+    pub async fn acquire(&self, url: &str) {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let slot = next_slot.get(&host).copied().filter(|slot| *slot > now).unwrap_or(now);
+            next_slot.insert(host, slot + self.min_interval);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
 /// 从URL获取文本内容
 #[tauri::command]
-pub async fn fetch_text_from_url(url: String) -> Result<String, String> {
+pub async fn fetch_text_from_url(url: String) -> AppResult<String> {
     let client = create_client()?;
     
     let response = client
@@ -23,17 +71,44 @@ pub async fn fetch_text_from_url(url: String) -> Result<String, String> {
         .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
         .send()
         .await
-        .map_err(|e| format!("请求失败: {}", e))?;
+        .map_err(|e| AppError::network("请求失败", e))?;
     
     if !response.status().is_success() {
-        return Err(format!("HTTP错误: {}", response.status()));
+        return Err(AppError::http_status("HTTP错误", response.status()));
     }
     
     let text = response
         .text()
         .await
-        .map_err(|e| format!("读取内容失败: {}", e))?;
+        .map_err(|e| AppError::network("读取内容失败", e))?;
     
     Ok(text)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn limiter_spaces_requests_per_host() {
+        let limiter = HostRateLimiter::per_second(20);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.acquire("https://auth.example.com/a").await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100), "{:?}", elapsed);
+
+        // 其他主机不受影响
+        let other = Instant::now();
+        limiter.acquire("https://portal.example.com/b").await;
+        assert!(other.elapsed() < Duration::from_millis(40));
+    }
+
+    #[test]
+    fn zero_rate_is_treated_as_one_per_second() {
+        assert_eq!(HostRateLimiter::per_second(0).min_interval, Duration::from_secs(1));
+    }
+}
@@ -3,12 +3,48 @@ mod http_client;
 mod config;
 mod augment_oauth;
 mod token_manager;
+mod storage;
+mod vault;
+mod token_db;
+mod token_schema;
+mod token_store;
+mod paths;
+mod error;
+mod logging;
+mod token_query;
+mod token_bulk;
+mod lifecycle;
+mod token_history;
+mod trash;
+mod token_import;
+mod remote_source;
+mod remote_mapping;
+mod remote_push;
+mod tombstone;
+mod token_sync;
+mod token_file;
+mod remote_schedule;
+mod health_check;
+mod token_refresh;
 
 // 导入命令
-use http_client::fetch_text_from_url;
+use http_client::{fetch_text_from_url, HostRateLimiter};
 use config::{load_config, save_config};
 use augment_oauth::extract_token_from_session;
-use token_manager::{read_tokens, write_tokens, add_token, import_from_remote, delete_token, update_token};
+use token_manager::{read_tokens, write_tokens, add_token, import_from_remote, delete_token, update_token, update_token_fields, store_info, list_tokens_backups, restore_tokens_backup};
+use vault::{vault_status, enable_vault, unlock_vault, lock_vault, change_passphrase};
+use token_query::{query_tokens, list_saved_filters, save_filter, delete_saved_filter};
+use token_bulk::{bulk_update_tokens, bulk_delete_tokens};
+use lifecycle::{check_token, evaluate_lifecycle, get_status_transitions};
+use token_history::get_token_history;
+use trash::{list_trash, restore_token, empty_trash};
+use remote_push::push_to_remote;
+use token_sync::sync_with_remote;
+use token_file::{export_tokens, import_tokens_from_file};
+use remote_schedule::{get_remote_source_status, list_remote_sources, save_remote_source, delete_remote_source};
+use token_refresh::{refresh_tokens, cancel_job};
+use token_store::TokenStore;
+use error::AppResult;
 use serde::{Deserialize, Serialize};
 use tauri::Manager;
 
@@ -23,8 +59,8 @@ pub struct TokenFromSessionResponse {
 
 /// 从 session 提取 token 的 Tauri 命令
 #[tauri::command]
-async fn parse_session(session: String) -> Result<TokenFromSessionResponse, String> {
-    println!("收到 parse_session 命令");
+async fn parse_session(session: String) -> AppResult<TokenFromSessionResponse> {
+    log::debug!("收到 parse_session 命令");
 
     let token_response = extract_token_from_session(&session).await?;
 
@@ -50,6 +86,27 @@ pub fn run() {
                 let _ = window.unminimize();
             }
         }))
+        .setup(|app| {
+            let data_dir = paths::init(app.handle())?;
+            logging::init(&data_dir)?;
+            paths::migrate_legacy_data()?;
+
+            // 启动时清理过期的回收站记录；保险库锁定时跳过，之后的写入会再次清理
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = handle.state::<TokenStore>().purge_trash().await {
+                    log::warn!("清理回收站失败: {}", e);
+                }
+            });
+
+            remote_schedule::start(app.handle().clone());
+            health_check::start(app.handle().clone());
+            lifecycle::start(app.handle().clone());
+
+            Ok(())
+        })
+        .manage(TokenStore::default())
+        .manage(HostRateLimiter::default())
         .invoke_handler(tauri::generate_handler![
             fetch_text_from_url,
             load_config,
@@ -60,7 +117,39 @@ pub fn run() {
             add_token,
             import_from_remote,
             delete_token,
-            update_token
+            update_token,
+            update_token_fields,
+            store_info,
+            list_tokens_backups,
+            restore_tokens_backup,
+            vault_status,
+            enable_vault,
+            unlock_vault,
+            lock_vault,
+            change_passphrase,
+            query_tokens,
+            list_saved_filters,
+            save_filter,
+            delete_saved_filter,
+            bulk_update_tokens,
+            bulk_delete_tokens,
+            check_token,
+            evaluate_lifecycle,
+            get_status_transitions,
+            get_token_history,
+            list_trash,
+            restore_token,
+            empty_trash,
+            push_to_remote,
+            sync_with_remote,
+            export_tokens,
+            import_tokens_from_file,
+            get_remote_source_status,
+            list_remote_sources,
+            save_remote_source,
+            delete_remote_source,
+            refresh_tokens,
+            cancel_job
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,457 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::augment_oauth::{extract_token_with_limiter, AugmentTokenResponse};
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::http_client::HostRateLimiter;
+use crate::token_manager::{now_timestamp, PortalInfo, TokenRecord};
+use crate::token_history::ChangeSource;
+use crate::token_manager::parse_timestamp;
+use crate::token_store::TokenStore;
+
+/// 每条记录保留的状态变更数量
+const MAX_STATUS_TRANSITIONS: usize = 50;
+
+/// 后台按当前时间重新评估状态的间隔
+const EVALUATE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// 后台评估改变了记录状态后发出的事件，负载为发生变更的记录数
+pub const LIFECYCLE_EVENT: &str = "lifecycle";
+
+/// 账号状态
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BanStatus {
+    Active,
+    Suspended,
+    Banned,
+    SessionExpired,
+    /// 积分已用完
+    Exhausted,
+    /// 已过有效期
+    Expired,
+    #[default]
+    Unknown,
+}
+
+impl BanStatus {
+    /// 解析任意写法的状态字符串，兼容旧数据和远端返回的非标准值
+    pub fn parse(value: &str) -> Self {
+        let normalized = value.trim().to_ascii_uppercase().replace(['-', ' '], "_");
+        match normalized.as_str() {
+            "ACTIVE" | "NORMAL" | "OK" | "VALID" => Self::Active,
+            "SUSPENDED" | "SUSPEND" => Self::Suspended,
+            "BANNED" | "BAN" | "BLOCKED" => Self::Banned,
+            "SESSION_EXPIRED" | "SESSION_INVALID" | "INVALID" => Self::SessionExpired,
+            "EXHAUSTED" | "NO_CREDITS" | "DEPLETED" => Self::Exhausted,
+            "EXPIRED" => Self::Expired,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "ACTIVE",
+            Self::Suspended => "SUSPENDED",
+            Self::Banned => "BANNED",
+            Self::SessionExpired => "SESSION_EXPIRED",
+            Self::Exhausted => "EXHAUSTED",
+            Self::Expired => "EXPIRED",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// 只能由一次成功的检查或手动修改解除的状态
+    fn is_sticky(&self) -> bool {
+        matches!(self, Self::Suspended | Self::Banned | Self::SessionExpired)
+    }
+}
+
+impl<'de> Deserialize<'de> for BanStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Option::<String>::deserialize(deserializer)?;
+        Ok(value.as_deref().map(Self::parse).unwrap_or_default())
+    }
+}
+
+/// 一次 Session 检查的结论
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CheckOutcome {
+    Ok,
+    SessionExpired,
+    Banned,
+    Suspended,
+    /// 尚未接受服务条款，需要手动处理，不影响账号状态
+    TermsNotAccepted,
+    /// 限流、服务端错误、网络错误、页面结构变化等临时失败，不影响账号状态
+    Failed,
+}
+
+impl CheckOutcome {
+    fn from_error(error: &AppError) -> Self {
+        match error.code {
+            ErrorCode::SessionInvalid => Self::SessionExpired,
+            ErrorCode::AccountBanned => Self::Banned,
+            ErrorCode::AccountSuspended => Self::Suspended,
+            ErrorCode::TermsNotAccepted => Self::TermsNotAccepted,
+            _ => Self::Failed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CheckResult {
+    pub checked_at: String,
+    pub outcome: CheckOutcome,
+    /// 失败时的错误码与说明
+    #[serde(default)]
+    pub error: Option<AppError>,
+}
+
+/// 状态变更记录
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StatusTransition {
+    pub from: BanStatus,
+    pub to: BanStatus,
+    pub at: String,
+    pub reason: String,
+    /// 是否为手动修改；手动设置的状态在下一次有结论的检查之前不会被自动评估覆盖
+    #[serde(default)]
+    pub manual: bool,
+}
+
+/// 当前状态是否为手动设置，且之后还没有得出结论的检查
+fn is_manual_override(token: &TokenRecord) -> bool {
+    let manual = token.status_transitions.last().is_some_and(|transition| transition.manual && transition.to == token.ban_status);
+    let concluded = token.last_check.as_ref().is_some_and(|check| {
+        !matches!(check.outcome, CheckOutcome::TermsNotAccepted | CheckOutcome::Failed)
+    });
+    manual && !concluded
+}
+
+/// 根据最近一次检查结果、有效期和积分推导账号状态，返回 (状态, 原因)
+pub fn evaluate(token: &TokenRecord, now: DateTime<Utc>) -> (BanStatus, &'static str) {
+    if is_manual_override(token) {
+        return (token.ban_status, "保持手动设置的状态");
+    }
+
+    match token.last_check.as_ref().map(|check| check.outcome) {
+        Some(CheckOutcome::Banned) => return (BanStatus::Banned, "检查结果: 账号已封禁"),
+        Some(CheckOutcome::Suspended) => return (BanStatus::Suspended, "检查结果: 账号已暂停"),
+        Some(CheckOutcome::SessionExpired) => return (BanStatus::SessionExpired, "检查结果: Session 已失效"),
+        Some(CheckOutcome::Ok) => {}
+        Some(CheckOutcome::TermsNotAccepted | CheckOutcome::Failed) | None => {
+            if token.ban_status.is_sticky() {
+                return (token.ban_status, "保持原状态");
+            }
+        }
+    }
+
+    let portal_info = token.portal_info.as_ref();
+
+    let expired = portal_info
+        .and_then(|info| info.expiry_date.as_deref())
+        .and_then(parse_timestamp)
+        .is_some_and(|expiry| expiry <= now);
+    if expired {
+        return (BanStatus::Expired, "已过有效期");
+    }
+
+    let exhausted = portal_info
+        .and_then(|info| info.credits_balance)
+        .is_some_and(|balance| balance <= 0);
+    if exhausted {
+        return (BanStatus::Exhausted, "积分已用完");
+    }
+
+    let checked_ok = token.last_check.as_ref().is_some_and(|check| check.outcome == CheckOutcome::Ok);
+    if !checked_ok && portal_info.is_none() && token.ban_status == BanStatus::Unknown {
+        return (BanStatus::Unknown, "缺少账号信息");
+    }
+
+    (BanStatus::Active, "账号正常")
+}
+
+/// 修改状态并记录变更，状态未变化时不做任何事；返回是否发生变更
+pub fn set_status(token: &mut TokenRecord, status: BanStatus, reason: &str) -> bool {
+    push_transition(token, status, reason, false)
+}
+
+fn push_transition(token: &mut TokenRecord, status: BanStatus, reason: &str, manual: bool) -> bool {
+    if token.ban_status == status {
+        return false;
+    }
+
+    log::info!(id = token.id.as_str(), from = token.ban_status.as_str(), to = status.as_str(); "账号状态变更: {}", reason);
+
+    token.status_transitions.push(StatusTransition {
+        from: token.ban_status,
+        to: status,
+        at: now_timestamp(),
+        reason: reason.to_string(),
+        manual,
+    });
+    if token.status_transitions.len() > MAX_STATUS_TRANSITIONS {
+        let overflow = token.status_transitions.len() - MAX_STATUS_TRANSITIONS;
+        token.status_transitions.drain(..overflow);
+    }
+
+    token.ban_status = status;
+    true
+}
+
+/// 手动指定状态：覆盖最近一次检查的结论，直到下一次检查
+pub fn set_status_manually(token: &mut TokenRecord, status: BanStatus) -> bool {
+    if token.ban_status == status {
+        return false;
+    }
+    token.last_check = None;
+    push_transition(token, status, "手动修改", true)
+}
+
+/// 重新评估记录状态；返回是否发生变更
+pub fn reevaluate(token: &mut TokenRecord, now: DateTime<Utc>) -> bool {
+    let (status, reason) = evaluate(token, now);
+    set_status(token, status, reason)
+}
+
+/// 把一次检查的结果写入记录：成功时更新 token 信息，并重新评估状态
+pub fn apply_check_result(token: &mut TokenRecord, result: &AppResult<AugmentTokenResponse>) {
+    let now = now_timestamp();
+
+    let (outcome, error) = match result {
+        Ok(response) => {
+            token.tenant_url = response.tenant_url.clone();
+            token.access_token = response.access_token.clone();
+            if response.email.is_some() {
+                token.email_note = response.email.clone();
+            }
+
+            // 未取到的积分信息保留旧值
+            let previous = token.portal_info.take().unwrap_or(PortalInfo {
+                credits_balance: None,
+                expiry_date: None,
+            });
+            token.portal_info = Some(PortalInfo {
+                credits_balance: response.credits_balance.or(previous.credits_balance),
+                expiry_date: response.expiry_date.clone().or(previous.expiry_date),
+            });
+
+            token.updated_at = now.clone();
+            (CheckOutcome::Ok, None)
+        }
+        Err(error) => (CheckOutcome::from_error(error), Some(error.clone())),
+    };
+
+    token.last_check = Some(CheckResult { checked_at: now, outcome, error });
+    reevaluate(token, Utc::now());
+}
+
+/// 使用记录的 auth_session 重新获取 token 信息并更新账号状态
+/// 检查失败时结果同样会被记录，随后返回原始错误；请求按主机限速
+/// 请求期间记录的 Session 被修改时丢弃本次结果，返回 InvalidState
+pub async fn check_record(store: &TokenStore, id: &str, limiter: &HostRateLimiter) -> AppResult<TokenRecord> {
+    let session = store.get(id).await?
+        .map(|token| token.auth_session)
+        .ok_or_else(|| AppError::not_found("未找到指定的 Token 记录"))?;
+
+    let result = extract_token_with_limiter(&session, Some(limiter)).await;
+
+    let updated = store.mutate(ChangeSource::Refresh, |tokens| {
+        let token = tokens.iter_mut()
+            .find(|token| token.id == id)
+            .ok_or_else(|| AppError::not_found("未找到指定的 Token 记录"))?;
+        // 请求不在锁内进行，期间记录可能已被编辑或导入覆盖
+        if token.auth_session != session {
+            return Err(AppError::invalid_state("检查期间 Session 已被修改，已丢弃本次检查结果"));
+        }
+        apply_check_result(token, &result);
+        Ok(token.clone())
+    }).await?;
+
+    result.map(|_| updated)
+}
+
+/// 检查单个账号
+#[tauri::command]
+pub async fn check_token(
+    store: State<'_, TokenStore>,
+    limiter: State<'_, HostRateLimiter>,
+    id: String,
+) -> AppResult<TokenRecord> {
+    check_record(&store, &id, &limiter).await
+}
+
+/// 按当前时间重新评估所有记录的状态（例如到期、积分耗尽），返回发生变更的记录数
+pub async fn evaluate_all(store: &TokenStore) -> AppResult<usize> {
+    let now = Utc::now();
+
+    // 先在快照上判断，没有变化时不写盘
+    let pending = store.list().await?
+        .iter()
+        .filter(|token| evaluate(token, now).0 != token.ban_status)
+        .count();
+    if pending == 0 {
+        return Ok(0);
+    }
+
+    store.mutate(ChangeSource::Lifecycle, |tokens| {
+        Ok(tokens.iter_mut().map(|token| reevaluate(token, now)).filter(|changed| *changed).count())
+    }).await
+}
+
+/// 立即重新评估所有记录的状态，返回发生变更的记录数
+#[tauri::command]
+pub async fn evaluate_lifecycle(store: State<'_, TokenStore>) -> AppResult<usize> {
+    evaluate_all(&store).await
+}
+
+/// 启动后台评估任务：启动时和之后每隔一段时间评估一次，有记录变更时通知前端刷新
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match evaluate_all(&app.state::<TokenStore>()).await {
+                Ok(0) => {}
+                Ok(changed) => {
+                    if let Err(e) = app.emit(LIFECYCLE_EVENT, changed) {
+                        log::warn!("发送状态评估事件失败: {}", e);
+                    }
+                }
+                // 保险库锁定时跳过，解锁后的下一轮再评估
+                Err(e) if e.code == ErrorCode::VaultLocked => {}
+                Err(e) => log::warn!("评估账号状态失败: {}", e),
+            }
+            tokio::time::sleep(EVALUATE_INTERVAL).await;
+        }
+    });
+}
+
+/// 获取记录的状态变更历史（从旧到新）
+#[tauri::command]
+pub async fn get_status_transitions(store: State<'_, TokenStore>, id: String) -> AppResult<Vec<StatusTransition>> {
+    store.get(&id).await?
+        .map(|token| token.status_transitions)
+        .ok_or_else(|| AppError::not_found("未找到指定的 Token 记录"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn token(portal_info: Option<PortalInfo>) -> TokenRecord {
+        TokenRecord {
+            ban_status: BanStatus::Unknown,
+            portal_info,
+            ..TokenRecord::test("a", "session-a")
+        }
+    }
+
+    fn portal(credits_balance: Option<i32>, expiry_date: Option<&str>) -> Option<PortalInfo> {
+        Some(PortalInfo { credits_balance, expiry_date: expiry_date.map(str::to_string) })
+    }
+
+    fn checked(token: &mut TokenRecord, outcome: CheckOutcome) {
+        token.last_check = Some(CheckResult { checked_at: now_timestamp(), outcome, error: None });
+    }
+
+    #[test]
+    fn parse_accepts_aliases() {
+        assert_eq!(BanStatus::parse("active"), BanStatus::Active);
+        assert_eq!(BanStatus::parse(" Normal "), BanStatus::Active);
+        assert_eq!(BanStatus::parse("suspend"), BanStatus::Suspended);
+        assert_eq!(BanStatus::parse("Blocked"), BanStatus::Banned);
+        assert_eq!(BanStatus::parse("session-expired"), BanStatus::SessionExpired);
+        assert_eq!(BanStatus::parse("session invalid"), BanStatus::SessionExpired);
+        assert_eq!(BanStatus::parse("no_credits"), BanStatus::Exhausted);
+        assert_eq!(BanStatus::parse("EXPIRED"), BanStatus::Expired);
+        assert_eq!(BanStatus::parse("whatever"), BanStatus::Unknown);
+        assert_eq!(BanStatus::parse(""), BanStatus::Unknown);
+    }
+
+    #[test]
+    fn deserialize_uses_parse_and_defaults_null() {
+        let status: BanStatus = serde_json::from_str("\"banned\"").unwrap();
+        assert_eq!(status, BanStatus::Banned);
+        let status: BanStatus = serde_json::from_str("null").unwrap();
+        assert_eq!(status, BanStatus::Unknown);
+    }
+
+    #[test]
+    fn check_outcome_overrides_portal_info() {
+        let mut token = token(portal(Some(100), None));
+        checked(&mut token, CheckOutcome::Banned);
+        assert_eq!(evaluate(&token, Utc::now()).0, BanStatus::Banned);
+        checked(&mut token, CheckOutcome::SessionExpired);
+        assert_eq!(evaluate(&token, Utc::now()).0, BanStatus::SessionExpired);
+    }
+
+    #[test]
+    fn sticky_status_survives_failed_checks() {
+        let mut token = token(portal(Some(100), None));
+        token.ban_status = BanStatus::Suspended;
+        checked(&mut token, CheckOutcome::Failed);
+        assert_eq!(evaluate(&token, Utc::now()).0, BanStatus::Suspended);
+
+        checked(&mut token, CheckOutcome::Ok);
+        assert_eq!(evaluate(&token, Utc::now()).0, BanStatus::Active);
+    }
+
+    #[test]
+    fn expiry_and_credits() {
+        let now = Utc::now();
+        let expired = token(portal(Some(100), Some("2000-01-01T00:00:00Z")));
+        assert_eq!(evaluate(&expired, now).0, BanStatus::Expired);
+
+        let exhausted = token(portal(Some(0), Some("2999-01-01T00:00:00Z")));
+        assert_eq!(evaluate(&exhausted, now).0, BanStatus::Exhausted);
+
+        let active = token(portal(Some(1), None));
+        assert_eq!(evaluate(&active, now).0, BanStatus::Active);
+
+        let unknown = token(None);
+        assert_eq!(evaluate(&unknown, now).0, BanStatus::Unknown);
+    }
+
+    #[test]
+    fn set_status_records_bounded_transitions() {
+        let mut token = token(None);
+        assert!(!set_status(&mut token, BanStatus::Unknown, "无变化"));
+        assert!(token.status_transitions.is_empty());
+
+        for i in 0..MAX_STATUS_TRANSITIONS + 5 {
+            let status = if i % 2 == 0 { BanStatus::Active } else { BanStatus::Exhausted };
+            assert!(set_status(&mut token, status, "测试"));
+        }
+        assert_eq!(token.status_transitions.len(), MAX_STATUS_TRANSITIONS);
+        assert_eq!(token.status_transitions.last().unwrap().to, token.ban_status);
+    }
+
+    #[test]
+    fn manual_status_clears_last_check() {
+        let mut token = token(None);
+        checked(&mut token, CheckOutcome::Banned);
+        assert!(set_status_manually(&mut token, BanStatus::Active));
+        assert!(token.last_check.is_none());
+        assert!(token.status_transitions.last().unwrap().manual);
+        assert_eq!(evaluate(&token, Utc::now()).0, BanStatus::Active);
+    }
+
+    #[test]
+    fn manual_status_survives_evaluation_until_next_check() {
+        let mut token = token(portal(Some(100), Some("2000-01-01T00:00:00Z")));
+        assert!(set_status_manually(&mut token, BanStatus::Active));
+        assert!(!reevaluate(&mut token, Utc::now()));
+        assert_eq!(token.ban_status, BanStatus::Active);
+
+        // 临时失败不算结论
+        checked(&mut token, CheckOutcome::Failed);
+        assert!(!reevaluate(&mut token, Utc::now()));
+
+        checked(&mut token, CheckOutcome::Ok);
+        assert!(reevaluate(&mut token, Utc::now()));
+        assert_eq!(token.ban_status, BanStatus::Expired);
+        assert!(!token.status_transitions.last().unwrap().manual);
+    }
+}
@@ -0,0 +1,195 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
+
+use crate::error::{AppError, AppResult};
+
+/// 覆盖数据目录的环境变量
+pub const DATA_DIR_ENV: &str = "AUG_SESSION_SYNC_DATA_DIR";
+
+/// 覆盖数据目录的命令行参数: --data-dir <路径> 或 --data-dir=<路径>
+const DATA_DIR_ARG: &str = "--data-dir";
+
+/// 便携模式参数：数据保存在可执行文件旁的 data 目录
+const PORTABLE_ARG: &str = "--portable";
+
+/// 旧版本使用过的数据目录名（位于 %APPDATA% 或 ~/.config 下）
+const LEGACY_DIR_NAMES: [&str; 2] = [
+    "com.lantianzhi.aug-session-sync",
+    "com.cubezhao.aug-session-sync",
+];
+
+/// 旧目录迁移完成后写入的标记文件
+const LEGACY_MIGRATED_MARKER: &str = ".legacy-migrated";
+
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+static IS_DEFAULT_DIR: OnceLock<bool> = OnceLock::new();
+
+/// 初始化应用数据目录，需在 setup 阶段调用一次
+/// 优先级: --data-dir > --portable > 环境变量 > Tauri 默认应用数据目录
+/// 旧数据迁移需在日志初始化后单独调用 migrate_legacy_data
+pub fn init(app: &AppHandle) -> AppResult<PathBuf> {
+    let (data_dir, is_default) = match override_dir()? {
+        Some(dir) => (dir, false),
+        None => {
+            let dir = app.path().app_data_dir()
+                .map_err(|e| AppError::io("获取应用数据目录失败", e))?;
+            (dir, true)
+        }
+    };
+
+    fs::create_dir_all(&data_dir)
+        .map_err(|e| AppError::io("创建应用数据目录失败", e))?;
+
+    let _ = IS_DEFAULT_DIR.set(is_default);
+
+    Ok(DATA_DIR.get_or_init(|| data_dir).clone())
+}
+
+/// 迁移旧版本数据目录中的文件
+/// 只有使用默认目录时才迁移，自定义目录视为用户明确指定的独立数据
+pub fn migrate_legacy_data() -> AppResult<()> {
+    let data_dir = data_dir()?;
+    log::info!("应用数据目录: {}", data_dir.display());
+
+    if IS_DEFAULT_DIR.get().copied().unwrap_or(false) {
+        migrate_legacy_dirs(&data_dir);
+    }
+    Ok(())
+}
+
+/// 获取应用数据目录
+pub fn data_dir() -> AppResult<PathBuf> {
+    DATA_DIR.get()
+        .cloned()
+        .ok_or_else(|| AppError::invalid_state("应用数据目录尚未初始化"))
+}
+
+/// 从命令行参数或环境变量解析自定义数据目录
+fn override_dir() -> AppResult<Option<PathBuf>> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == DATA_DIR_ARG {
+            let value = args.next()
+                .ok_or_else(|| AppError::invalid_input(format!("{} 参数缺少路径", DATA_DIR_ARG)))?;
+            return Ok(Some(absolute(PathBuf::from(value))?));
+        }
+        if let Some(value) = arg.strip_prefix(&format!("{}=", DATA_DIR_ARG)) {
+            return Ok(Some(absolute(PathBuf::from(value))?));
+        }
+        if arg == PORTABLE_ARG {
+            return Ok(Some(exe_dir()?.join("data")));
+        }
+    }
+
+    match env::var(DATA_DIR_ENV) {
+        Ok(value) if !value.trim().is_empty() => Ok(Some(absolute(PathBuf::from(value))?)),
+        _ => Ok(None),
+    }
+}
+
+/// 可执行文件所在目录
+fn exe_dir() -> AppResult<PathBuf> {
+    let exe = env::current_exe()
+        .map_err(|e| AppError::io("获取可执行文件路径失败", e))?;
+    exe.parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| AppError::io("获取可执行文件目录失败", exe.display()))
+}
+
+/// 相对路径按可执行文件目录解析，保证便携模式下不受启动目录影响
+fn absolute(path: PathBuf) -> AppResult<PathBuf> {
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        Ok(exe_dir()?.join(path))
+    }
+}
+
+/// 旧版本手动拼接的数据目录
+fn legacy_dirs() -> Vec<PathBuf> {
+    let Ok(base) = env::var("APPDATA")
+        .or_else(|_| env::var("HOME").map(|home| format!("{}/.config", home)))
+    else {
+        return vec![];
+    };
+
+    LEGACY_DIR_NAMES.iter()
+        .map(|name| PathBuf::from(&base).join(name))
+        .collect()
+}
+
+/// 一次性把旧目录中的文件移动到新目录；新目录中已存在的同名文件不会被覆盖
+fn migrate_legacy_dirs(data_dir: &Path) {
+    let marker = data_dir.join(LEGACY_MIGRATED_MARKER);
+    if marker.exists() {
+        return;
+    }
+
+    for legacy_dir in legacy_dirs() {
+        // Windows 下新目录可能与旧目录相同
+        if !legacy_dir.is_dir() || same_dir(&legacy_dir, data_dir) {
+            continue;
+        }
+
+        let Ok(entries) = fs::read_dir(&legacy_dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let source = entry.path();
+            if !source.is_file() {
+                continue;
+            }
+
+            let target = data_dir.join(entry.file_name());
+            if target.exists() {
+                log::warn!("跳过迁移（目标已存在）: {}", source.display());
+                continue;
+            }
+
+            match move_file(&source, &target) {
+                Ok(()) => log::info!("已迁移: {} -> {}", source.display(), target.display()),
+                Err(e) => log::error!("迁移失败: {}: {}", source.display(), e),
+            }
+        }
+
+        // 目录已清空时顺便删除
+        let _ = fs::remove_dir(&legacy_dir);
+    }
+
+    let _ = fs::write(&marker, chrono::Local::now().to_rfc3339());
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// 移动文件，跨文件系统时退化为复制后删除
+fn move_file(source: &Path, target: &Path) -> std::io::Result<()> {
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    fs::copy(source, target)?;
+    fs::remove_file(source)
+}
+
+/// 读写数据目录的测试共用同一个临时目录，需要依次运行
+#[cfg(test)]
+static TEST_DIR_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// 独占并清空测试数据目录，返回的锁在测试结束前需一直持有
+#[cfg(test)]
+pub async fn lock_test_data_dir() -> tokio::sync::MutexGuard<'static, ()> {
+    let guard = TEST_DIR_LOCK.lock().await;
+    let dir = DATA_DIR.get_or_init(|| env::temp_dir().join(format!("aug-session-sync-test-{}", std::process::id())));
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("创建测试数据目录失败");
+    guard
+}
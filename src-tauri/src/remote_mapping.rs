@@ -0,0 +1,343 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::error::{AppError, AppResult, ErrorCode};
+
+/// 可以映射的 TokenRecord 字段，portal_info 的子字段以点号连接
+const MAPPABLE_FIELDS: &[&str] = &[
+    "id",
+    "tenant_url",
+    "access_token",
+    "created_at",
+    "updated_at",
+    "portal_url",
+    "ban_status",
+    "portal_info",
+    "portal_info.credits_balance",
+    "portal_info.expiry_date",
+    "email_note",
+    "tag_name",
+    "tag_color",
+    "auth_session",
+    "suspensions",
+    "skip_check",
+    "balance_color_mode",
+];
+
+/// 默认兼容的其他字段名：字段未配置映射且记录中没有同名字段时读取
+const DEFAULT_FIELD_ALIASES: &[(&str, &str)] = &[
+    ("tenant_url", "tenantUrl"),
+    ("access_token", "accessToken"),
+    ("auth_session", "authSession"),
+    ("email_note", "emailNote"),
+    ("ban_status", "banStatus"),
+];
+
+/// 取值为数字时需要转为字符串的字段（远端常用数字 id）
+const STRING_FIELDS: &[&str] = &["id", "tenant_url", "access_token", "created_at", "updated_at", "auth_session"];
+
+/// 判断响应是否成功的条件
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SuccessCondition {
+    /// 兼容原有格式：响应是对象时要求 status 等于 1，响应本身是数组时只看 HTTP 状态码
+    Auto,
+    /// 只看 HTTP 状态码
+    HttpStatus,
+    /// 指定位置的值等于 expected，可以是布尔值、字符串或数字
+    Equals {
+        pointer: String,
+        expected: Value,
+    },
+}
+
+/// 远端响应的声明式映射
+/// 路径以 / 开头时按 JSON Pointer 解析，否则按点号分隔的字段名解析，例如 user.email
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RemoteMapping {
+    /// 记录数组在响应中的位置，为空表示响应本身就是数组
+    #[serde(default = "default_records_path")]
+    pub records_path: String,
+    #[serde(default = "default_success")]
+    pub success: SuccessCondition,
+    /// TokenRecord 字段 -> 记录中的来源路径；未配置的字段按同名字段读取，部分字段兼容驼峰写法（见 DEFAULT_FIELD_ALIASES）
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+fn default_records_path() -> String {
+    "data".to_string()
+}
+
+fn default_success() -> SuccessCondition {
+    SuccessCondition::Auto
+}
+
+impl Default for RemoteMapping {
+    /// 兼容原有格式：{ "status": 1, "data": [...] }
+    fn default() -> Self {
+        Self {
+            records_path: default_records_path(),
+            success: default_success(),
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+/// 把路径转换为 JSON Pointer
+fn to_pointer(path: &str) -> String {
+    let path = path.trim();
+    if path.is_empty() || path.starts_with('/') {
+        return path.to_string();
+    }
+    path.split('.')
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+impl RemoteMapping {
+    /// 检查映射中的字段名是否有效
+    pub fn validate(&self) -> AppResult<()> {
+        let unknown: Vec<&str> = self.fields.keys()
+            .map(String::as_str)
+            .filter(|field| !MAPPABLE_FIELDS.contains(field))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(AppError::invalid_input("字段映射中包含未知字段")
+                .with_details(unknown.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// 从单页响应中取出记录
+    /// 响应本身是数组且记录位置为默认值时直接按记录数组处理；成功条件只有 Auto 会随响应结构变化
+    /// 返回按字段映射转换后的记录，不在这里反序列化，字段类型错误由调用方按记录处理
+    pub fn extract(&self, body: &Value) -> AppResult<Vec<Value>> {
+        self.check_success(body)?;
+
+        let records_path = self.records_path.trim();
+        let records = if records_path.is_empty() || (body.is_array() && records_path == default_records_path()) {
+            body
+        } else {
+            body.pointer(&to_pointer(&self.records_path))
+                .ok_or_else(|| AppError::parse("远端响应中未找到记录数组", format!("path = {}", self.records_path)))?
+        };
+        let records = records.as_array()
+            .ok_or_else(|| AppError::parse("远端响应中的记录不是数组", format!("path = {}", self.records_path)))?;
+
+        Ok(records.iter().map(|record| self.map_record(record)).collect())
+    }
+
+    fn check_success(&self, body: &Value) -> AppResult<()> {
+        let (pointer, expected) = match &self.success {
+            SuccessCondition::HttpStatus => return Ok(()),
+            SuccessCondition::Auto if body.is_array() => return Ok(()),
+            SuccessCondition::Auto => ("/status", &Value::from(1)),
+            SuccessCondition::Equals { pointer, expected } => (pointer.as_str(), expected),
+        };
+
+        let actual = body.pointer(&to_pointer(pointer)).unwrap_or(&Value::Null);
+        if actual == expected {
+            return Ok(());
+        }
+
+        log::warn!(pointer = pointer, actual = actual.to_string().as_str(); "远端 API 返回失败状态");
+        Err(AppError::new(ErrorCode::HttpStatus, "远端 API 返回失败状态")
+            .with_details(format!("{} = {}", pointer, actual)))
+    }
+
+    /// 按映射把一条远端记录转换为标准字段名的对象
+    pub(crate) fn map_record(&self, record: &Value) -> Value {
+        let mut mapped = match record {
+            Value::Object(map) => map.clone(),
+            _ if self.fields.is_empty() => return record.clone(),
+            _ => Map::new(),
+        };
+
+        for (field, alias) in DEFAULT_FIELD_ALIASES {
+            if self.fields.contains_key(*field) || mapped.contains_key(*field) {
+                continue;
+            }
+            if let Some(value) = mapped.get(*alias).cloned() {
+                mapped.insert(field.to_string(), value);
+            }
+        }
+
+        for (field, path) in &self.fields {
+            let Some(value) = record.pointer(&to_pointer(path)) else {
+                continue;
+            };
+            let value = match value {
+                Value::Number(number) if STRING_FIELDS.contains(&field.as_str()) => Value::String(number.to_string()),
+                value => value.clone(),
+            };
+
+            match field.split_once('.') {
+                Some((parent, child)) => {
+                    let parent = mapped.entry(parent)
+                        .and_modify(|existing| {
+                            if !existing.is_object() {
+                                *existing = Value::Object(Map::new());
+                            }
+                        })
+                        .or_insert_with(|| Value::Object(Map::new()));
+                    if let Value::Object(parent) = parent {
+                        parent.insert(child.to_string(), value);
+                    }
+                }
+                None => {
+                    mapped.insert(field.clone(), value);
+                }
+            }
+        }
+
+        Value::Object(mapped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_manager::RemoteTokenRecord;
+    use serde_json::json;
+
+    fn mapping(value: Value) -> RemoteMapping {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn default_mapping_accepts_legacy_envelope() {
+        let body = json!({ "status": 1, "data": [{ "id": "a" }, { "id": "b" }] });
+        let records = RemoteMapping::default().extract(&body).unwrap();
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn default_mapping_rejects_failed_envelope() {
+        let body = json!({ "status": 0, "data": [] });
+        let error = RemoteMapping::default().extract(&body).unwrap_err();
+        assert_eq!(error.code, ErrorCode::HttpStatus);
+    }
+
+    #[test]
+    fn bare_array_uses_http_status_only() {
+        let body = json!([{ "id": "a" }]);
+        assert_eq!(RemoteMapping::default().extract(&body).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn explicit_equals_is_checked_for_bare_array() {
+        let mapping = mapping(json!({
+            "success": { "type": "equals", "pointer": "/status", "expected": 1 }
+        }));
+        assert!(mapping.extract(&json!([{ "id": "a" }])).is_err());
+    }
+
+    #[test]
+    fn http_status_ignores_envelope_status() {
+        let mapping = mapping(json!({ "success": { "type": "http_status" } }));
+        let body = json!({ "status": 0, "data": [{ "id": "a" }] });
+        assert_eq!(mapping.extract(&body).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn nested_records_path_and_fields() {
+        let mapping = mapping(json!({
+            "records_path": "result.items",
+            "success": { "type": "http_status" },
+            "fields": {
+                "auth_session": "/session/value",
+                "email_note": "user.email",
+                "portal_info.credits_balance": "credits"
+            }
+        }));
+        let body = json!({
+            "result": { "items": [{
+                "session": { "value": "s1" },
+                "user": { "email": "a@example.com" },
+                "credits": 42
+            }] }
+        });
+
+        let records = mapping.extract(&body).unwrap();
+        assert_eq!(records[0]["auth_session"], "s1");
+        assert_eq!(records[0]["email_note"], "a@example.com");
+        assert_eq!(records[0]["portal_info"]["credits_balance"], 42);
+    }
+
+    #[test]
+    fn default_aliases_fill_missing_fields() {
+        let records = RemoteMapping::default().extract(&json!([
+            { "tenantUrl": "https://a.example.com", "authSession": "s1", "emailNote": "a@example.com" },
+            { "tenant_url": "https://b.example.com", "tenantUrl": "https://ignored.example.com" }
+        ])).unwrap();
+        assert_eq!(records[0]["tenant_url"], "https://a.example.com");
+        assert_eq!(records[0]["auth_session"], "s1");
+        assert_eq!(records[0]["email_note"], "a@example.com");
+        // 同名字段优先
+        assert_eq!(records[1]["tenant_url"], "https://b.example.com");
+
+        // 显式配置的映射优先于默认别名
+        let mapping = mapping(json!({ "fields": { "tenant_url": "host" } }));
+        let records = mapping.extract(&json!([{ "tenantUrl": "alias", "host": "mapped" }])).unwrap();
+        assert_eq!(records[0]["tenant_url"], "mapped");
+    }
+
+    #[test]
+    fn numeric_id_is_converted_to_string() {
+        let mapping = mapping(json!({ "fields": { "id": "uid" } }));
+        let records = mapping.extract(&json!([{ "uid": 7 }])).unwrap();
+        assert_eq!(records[0]["id"], "7");
+    }
+
+    #[test]
+    fn bool_success_condition() {
+        let mapping = mapping(json!({
+            "records_path": "items",
+            "success": { "type": "equals", "pointer": "ok", "expected": true }
+        }));
+        assert!(mapping.extract(&json!({ "ok": true, "items": [] })).is_ok());
+        assert!(mapping.extract(&json!({ "ok": false, "items": [] })).is_err());
+        assert!(mapping.extract(&json!({ "items": [] })).is_err());
+    }
+
+    #[test]
+    fn string_success_condition() {
+        let mapping = mapping(json!({
+            "success": { "type": "equals", "pointer": "/result", "expected": "success" }
+        }));
+        assert!(mapping.extract(&json!({ "result": "success", "data": [] })).is_ok());
+        assert!(mapping.extract(&json!({ "result": "error", "data": [] })).is_err());
+    }
+
+    #[test]
+    fn missing_records_path_is_an_error() {
+        let mapping = mapping(json!({ "records_path": "items", "success": { "type": "http_status" } }));
+        let error = mapping.extract(&json!({ "data": [] })).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Parse);
+    }
+
+    #[test]
+    fn bad_field_only_fails_its_record() {
+        let mapping = mapping(json!({ "fields": { "skip_check": "skip" } }));
+        let body = json!([
+            { "id": "a", "auth_session": "s1", "created_at": "2024-01-01T00:00:00Z", "skip": true },
+            { "id": "b", "auth_session": "s2", "created_at": "2024-01-01T00:00:00Z", "skip": "yes" }
+        ]);
+
+        let records = mapping.extract(&body).unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(RemoteTokenRecord::convert(&records[0], 0, false).is_ok());
+
+        let error = RemoteTokenRecord::convert(&records[1], 1, false).unwrap_err();
+        assert_eq!(error.index, 1);
+        assert!(error.reason.is_some());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_fields() {
+        let mapping = mapping(json!({ "fields": { "password": "pwd" } }));
+        assert_eq!(mapping.validate().unwrap_err().code, ErrorCode::InvalidInput);
+    }
+}
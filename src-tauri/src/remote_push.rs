@@ -0,0 +1,230 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::error::{AppError, AppResult};
+use crate::remote_source::{HttpMethod, RemoteRequest};
+use crate::storage;
+use crate::token_manager::now_timestamp;
+use crate::token_store::TokenStore;
+
+/// 每批默认推送的记录数
+const DEFAULT_BATCH_SIZE: usize = 50;
+
+/// 推送期间持有，避免并发推送同时改写推送记录
+static PUSH_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// 某条记录最近一次成功推送的内容
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PushedRecord {
+    /// 推送内容的 SHA-256
+    fingerprint: String,
+    pushed_at: String,
+}
+
+/// 推送记录：远端地址 -> 记录 id -> 最近一次推送
+type PushState = BTreeMap<String, BTreeMap<String, PushedRecord>>;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PushStatus {
+    Pushed,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PushItemResult {
+    pub id: String,
+    pub status: PushStatus,
+    pub error: Option<AppError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushResult {
+    pub pushed: usize,
+    pub failed: usize,
+    /// 自上次推送后没有变化而跳过的记录数
+    pub unchanged: usize,
+    /// 本次尝试推送的每条记录的结果
+    pub results: Vec<PushItemResult>,
+}
+
+/// 远端对单条记录的处理结果，响应体中可选的 results 数组
+#[derive(Debug, Deserialize)]
+struct RemoteItemResult {
+    id: String,
+    #[serde(default = "default_true")]
+    success: bool,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemotePushResponse {
+    #[serde(default)]
+    results: Vec<RemoteItemResult>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 获取推送记录文件路径
+/// 路径: <应用数据目录>/push_state.json
+fn get_push_state_path() -> AppResult<PathBuf> {
+    Ok(crate::paths::data_dir()?.join("push_state.json"))
+}
+
+fn load_push_state() -> AppResult<PushState> {
+    let path = get_push_state_path()?;
+    if !path.exists() {
+        return Ok(PushState::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| AppError::io("读取推送记录失败", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| AppError::parse("解析推送记录失败", e))
+}
+
+fn save_push_state(state: &PushState) -> AppResult<()> {
+    let content = serde_json::to_string_pretty(state)
+        .map_err(|e| AppError::parse("序列化推送记录失败", e))?;
+    storage::atomic_write(&get_push_state_path()?, content.as_bytes())
+}
+
+pub(crate) fn fingerprint(payload: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(payload.to_string().as_bytes());
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// 推送一批记录，返回每条记录的结果
+/// HTTP 请求失败时整批失败；响应体中带 results 时按其中的结果逐条判断，未列出的记录视为成功
+async fn push_batch(
+    client: &reqwest::Client,
+    url: &Url,
+    request: &RemoteRequest,
+    method: HttpMethod,
+    batch: &[(String, Value)],
+) -> Vec<PushItemResult> {
+    let payload: Vec<&Value> = batch.iter().map(|(_, payload)| payload).collect();
+    let response = request.build_with(client, url.clone(), method)
+        .json(&serde_json::json!({ "data": payload }))
+        .send()
+        .await;
+
+    let outcome = match response {
+        Err(e) => Err(AppError::network("推送到远端 API 失败", e)),
+        Ok(response) if !response.status().is_success() => {
+            Err(AppError::http_status("远端 API 返回错误", response.status()))
+        }
+        Ok(response) => {
+            // 响应体无法解析时只以 HTTP 状态为准
+            let text = response.text().await.unwrap_or_default();
+            let remote: HashMap<String, RemoteItemResult> = serde_json::from_str::<RemotePushResponse>(&text)
+                .map(|body| body.results.into_iter().map(|item| (item.id.clone(), item)).collect())
+                .unwrap_or_default();
+            Ok(remote)
+        }
+    };
+
+    batch.iter()
+        .map(|(id, _)| {
+            let error = match &outcome {
+                Err(error) => Some(error.clone()),
+                Ok(remote) => remote.get(id)
+                    .filter(|item| !item.success)
+                    .map(|item| AppError::invalid_state("远端拒绝了该记录")
+                        .with_details(item.message.as_deref().unwrap_or("未提供原因"))),
+            };
+            PushItemResult {
+                id: id.clone(),
+                status: if error.is_some() { PushStatus::Failed } else { PushStatus::Pushed },
+                error,
+            }
+        })
+        .collect()
+}
+
+/// 把新增或有变化的记录推送到远端 API
+/// 请求体为 { "data": [...] }，结构与导入时接受的 RemoteTokenRecord 一致
+/// request 中的 GET 按 POST 处理；ids 为空时推送全部记录；force 为 true 时忽略推送记录，全部重新推送
+#[tauri::command]
+pub async fn push_to_remote(
+    store: State<'_, TokenStore>,
+    api_url: String,
+    request: Option<RemoteRequest>,
+    ids: Option<Vec<String>>,
+    batch_size: Option<usize>,
+    force: Option<bool>,
+) -> AppResult<PushResult> {
+    let request = request.unwrap_or_default();
+    let method = match request.method {
+        HttpMethod::Get => HttpMethod::Post,
+        method => method,
+    };
+    let url = Url::parse(&api_url)
+        .map_err(|e| AppError::invalid_input("远端 API 地址无效").with_details(e))?;
+    let batch_size = batch_size.unwrap_or(DEFAULT_BATCH_SIZE).max(1);
+    let force = force.unwrap_or(false);
+
+    let _guard = PUSH_LOCK.lock().await;
+    let mut state = load_push_state()?;
+    let mut pushed_before = state.remove(&api_url).unwrap_or_default();
+
+    let selected: Option<HashSet<String>> = ids.map(|ids| ids.into_iter().collect());
+    let mut pending = Vec::new();
+    let mut unchanged = 0;
+    for token in store.list().await? {
+        if selected.as_ref().is_some_and(|ids| !ids.contains(&token.id)) {
+            continue;
+        }
+
+        let payload = token.to_remote_value()?;
+        let fingerprint = fingerprint(&payload);
+        if !force && pushed_before.get(&token.id).is_some_and(|pushed| pushed.fingerprint == fingerprint) {
+            unchanged += 1;
+            continue;
+        }
+        pending.push((token.id, payload, fingerprint));
+    }
+
+    log::info!(url = api_url.as_str(), pending = pending.len(), unchanged = unchanged; "开始推送到远端 API");
+
+    let client = crate::http_client::create_client()?;
+    let mut results = Vec::new();
+    for chunk in pending.chunks(batch_size) {
+        let batch: Vec<(String, Value)> = chunk.iter()
+            .map(|(id, payload, _)| (id.clone(), payload.clone()))
+            .collect();
+        let batch_results = push_batch(&client, &url, &request, method, &batch).await;
+
+        // 每批完成后立即记录，后续批次失败时已推送的记录不会重复推送
+        let pushed_at = now_timestamp();
+        for ((id, _, fingerprint), result) in chunk.iter().zip(&batch_results) {
+            if result.status == PushStatus::Pushed {
+                pushed_before.insert(id.clone(), PushedRecord {
+                    fingerprint: fingerprint.clone(),
+                    pushed_at: pushed_at.clone(),
+                });
+            }
+        }
+        state.insert(api_url.clone(), pushed_before.clone());
+        save_push_state(&state)?;
+
+        results.extend(batch_results);
+    }
+
+    let pushed = results.iter().filter(|result| result.status == PushStatus::Pushed).count();
+    let failed = results.len() - pushed;
+    log::info!(url = api_url.as_str(), pushed = pushed, failed = failed, unchanged = unchanged; "推送完成");
+
+    Ok(PushResult { pushed, failed, unchanged, results })
+}
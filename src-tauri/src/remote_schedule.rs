@@ -0,0 +1,237 @@
+use chrono::Utc;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::remote_mapping::RemoteMapping;
+use crate::remote_source::RemoteRequest;
+use crate::storage;
+use crate::token_import::MergeStrategy;
+use crate::token_manager::{self, now_timestamp, ImportResult};
+use crate::token_manager::parse_timestamp;
+use crate::token_store::TokenStore;
+
+/// 检查是否有到期导入源的间隔
+const TICK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 每次定时导入完成（无论成功或失败）后发出的事件
+pub const REMOTE_IMPORT_EVENT: &str = "remote-import";
+
+/// 保存在配置中的命名远端导入源
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteSource {
+    /// 导入源名称，用于区分各个导入源的状态，需唯一
+    pub name: String,
+    pub url: String,
+    /// 请求头、请求方式和分页
+    #[serde(default)]
+    pub request: RemoteRequest,
+    #[serde(default)]
+    pub mapping: RemoteMapping,
+    #[serde(default)]
+    pub strategy: MergeStrategy,
+    #[serde(default)]
+    pub generate_missing: bool,
+    /// 导入间隔（分钟），0 表示不自动导入
+    #[serde(default)]
+    pub interval_minutes: u32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 导入源最近一次运行的状态
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SourceStatus {
+    pub last_run_at: Option<String>,
+    pub last_success_at: Option<String>,
+    pub last_failure_at: Option<String>,
+    /// 最近一次失败的原因，成功后清除
+    pub last_error: Option<AppError>,
+}
+
+/// 导入源状态：名称 -> 状态
+type ScheduleState = BTreeMap<String, SourceStatus>;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RemoteImportEvent {
+    pub source: String,
+    pub result: Option<ImportResult>,
+    pub error: Option<AppError>,
+}
+
+/// 获取导入源状态文件路径
+/// 路径: <应用数据目录>/remote_sources.json
+fn get_state_path() -> AppResult<PathBuf> {
+    Ok(crate::paths::data_dir()?.join("remote_sources.json"))
+}
+
+fn load_state() -> AppResult<ScheduleState> {
+    let path = get_state_path()?;
+    if !path.exists() {
+        return Ok(ScheduleState::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| AppError::io("读取导入源状态失败", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| AppError::parse("解析导入源状态失败", e))
+}
+
+fn save_state(state: &ScheduleState) -> AppResult<()> {
+    let content = serde_json::to_string_pretty(state)
+        .map_err(|e| AppError::parse("序列化导入源状态失败", e))?;
+    storage::atomic_write(&get_state_path()?, content.as_bytes())
+}
+
+/// 距上次运行已超过导入间隔的导入源视为到期
+fn is_due(source: &RemoteSource, status: Option<&SourceStatus>) -> bool {
+    if !source.enabled || source.interval_minutes == 0 {
+        return false;
+    }
+
+    let last_run = status
+        .and_then(|status| status.last_run_at.as_deref())
+        .and_then(parse_timestamp);
+    match last_run {
+        Some(last_run) => Utc::now() - last_run >= chrono::Duration::minutes(source.interval_minutes.into()),
+        None => true,
+    }
+}
+
+/// 运行一个导入源并记录结果
+async fn run_source(app: &AppHandle, source: &RemoteSource, state: &mut ScheduleState) -> AppResult<()> {
+    let store = app.state::<TokenStore>();
+    let outcome = token_manager::run_remote_import(
+        &store,
+        &source.url,
+        &source.request,
+        &source.mapping,
+        source.strategy,
+        false,
+        source.generate_missing,
+    ).await;
+
+    let now = now_timestamp();
+    let status = state.entry(source.name.clone()).or_default();
+    status.last_run_at = Some(now.clone());
+    let event = match outcome {
+        Ok(result) => {
+            status.last_success_at = Some(now);
+            status.last_error = None;
+            RemoteImportEvent { source: source.name.clone(), result: Some(result), error: None }
+        }
+        Err(e) => {
+            log::warn!(source = source.name.as_str(); "定时导入失败: {}", e);
+            status.last_failure_at = Some(now);
+            status.last_error = Some(e.clone());
+            RemoteImportEvent { source: source.name.clone(), result: None, error: Some(e) }
+        }
+    };
+    save_state(state)?;
+
+    if let Err(e) = app.emit(REMOTE_IMPORT_EVENT, event) {
+        log::warn!("发送导入事件失败: {}", e);
+    }
+    Ok(())
+}
+
+/// 检查一次全部导入源，依次运行到期的导入源
+async fn tick(app: &AppHandle) -> AppResult<()> {
+    let sources = crate::config::load_config()?.remote_sources;
+    let mut state = load_state()?;
+
+    // 清理已删除的导入源的状态
+    let before = state.len();
+    state.retain(|name, _| sources.iter().any(|source| &source.name == name));
+    if state.len() != before {
+        save_state(&state)?;
+    }
+
+    for source in &sources {
+        if !is_due(source, state.get(&source.name)) {
+            continue;
+        }
+
+        // 保险库锁定时无法写入，跳过本轮且不计为失败，解锁后再导入
+        if let Err(e) = app.state::<TokenStore>().info().await {
+            if e.code == ErrorCode::VaultLocked {
+                log::debug!("保险库已锁定，跳过定时导入");
+                return Ok(());
+            }
+            return Err(e);
+        }
+
+        log::info!(source = source.name.as_str(); "开始定时导入");
+        run_source(app, source, &mut state).await?;
+    }
+
+    Ok(())
+}
+
+/// 启动定时导入任务，每分钟检查一次配置中到期的导入源
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Err(e) = tick(&app).await {
+                log::warn!("定时导入检查失败: {}", e);
+            }
+            tokio::time::sleep(TICK_INTERVAL).await;
+        }
+    });
+}
+
+/// 获取各导入源最近一次运行的状态
+#[tauri::command]
+pub fn get_remote_source_status() -> AppResult<BTreeMap<String, SourceStatus>> {
+    load_state()
+}
+
+/// 获取配置中的全部导入源
+#[tauri::command]
+pub fn list_remote_sources() -> AppResult<Vec<RemoteSource>> {
+    Ok(crate::config::load_config()?.remote_sources)
+}
+
+/// 保存导入源，同名时覆盖
+#[tauri::command]
+pub fn save_remote_source(source: RemoteSource) -> AppResult<()> {
+    let name = source.name.trim();
+    if name.is_empty() {
+        return Err(AppError::invalid_input("导入源名称不能为空"));
+    }
+    Url::parse(&source.url)
+        .map_err(|e| AppError::invalid_input("远端 API 地址无效").with_details(e))?;
+    source.mapping.validate()?;
+
+    let source = RemoteSource { name: name.to_string(), ..source };
+    crate::config::update_config(|config| {
+        match config.remote_sources.iter_mut().find(|saved| saved.name == source.name) {
+            Some(saved) => *saved = source,
+            None => config.remote_sources.push(source),
+        }
+        Ok(())
+    })
+}
+
+/// 删除导入源，其运行状态在下一次检查时清理
+#[tauri::command]
+pub fn delete_remote_source(name: String) -> AppResult<()> {
+    crate::config::update_config(|config| {
+        let before = config.remote_sources.len();
+        config.remote_sources.retain(|source| source.name != name);
+
+        if config.remote_sources.len() == before {
+            return Err(AppError::not_found(format!("未找到导入源: {}", name)));
+        }
+        Ok(())
+    })
+}
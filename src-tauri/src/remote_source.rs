@@ -0,0 +1,413 @@
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::error::{AppError, AppResult};
+use crate::logging;
+use crate::remote_mapping::RemoteMapping;
+
+/// 分页请求的默认页数上限，防止远端一直返回下一页导致死循环
+const DEFAULT_MAX_PAGES: u32 = 100;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    #[default]
+    Get,
+    Post,
+    Put,
+}
+
+/// 分页方式；页码和游标以查询参数的形式附加到请求 URL 上
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Pagination {
+    /// 不分页，只请求一次
+    #[default]
+    None,
+    /// 按页码翻页，返回空页或不足一页时结束
+    Page {
+        #[serde(default = "default_page_param")]
+        param: String,
+        #[serde(default = "default_first_page")]
+        first_page: u32,
+        /// 每页数量的参数名，为空时不发送
+        #[serde(default)]
+        size_param: Option<String>,
+        #[serde(default)]
+        page_size: Option<u32>,
+    },
+    /// 按游标翻页，响应中取不到游标时结束
+    Cursor {
+        #[serde(default = "default_cursor_param")]
+        param: String,
+        /// 下一页游标在响应中的位置（JSON Pointer）
+        #[serde(default = "default_cursor_pointer")]
+        cursor_pointer: String,
+    },
+    /// 按响应中的下一页链接翻页，链接可以是相对地址
+    NextLink {
+        /// 下一页链接在响应中的位置（JSON Pointer）
+        #[serde(default = "default_next_pointer")]
+        next_pointer: String,
+    },
+}
+
+fn default_page_param() -> String {
+    "page".to_string()
+}
+
+fn default_first_page() -> u32 {
+    1
+}
+
+fn default_cursor_param() -> String {
+    "cursor".to_string()
+}
+
+fn default_cursor_pointer() -> String {
+    "/next_cursor".to_string()
+}
+
+fn default_next_pointer() -> String {
+    "/next".to_string()
+}
+
+/// 远端 API 的请求配置
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct RemoteRequest {
+    #[serde(default)]
+    pub method: HttpMethod,
+    /// 附加的请求头，例如 Authorization 或 X-Api-Key
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// POST 请求的 JSON 请求体
+    #[serde(default)]
+    pub body: Option<Value>,
+    #[serde(default)]
+    pub pagination: Pagination,
+    /// 最多请求的页数，默认 100
+    #[serde(default)]
+    pub max_pages: Option<u32>,
+}
+
+impl RemoteRequest {
+    fn build(&self, client: &Client, url: Url) -> RequestBuilder {
+        let request = self.build_with(client, url, self.method);
+        match &self.body {
+            Some(body) => request.json(body),
+            None => request,
+        }
+    }
+
+    /// 按指定方式构建请求并附加配置的请求头，不附加请求体
+    pub fn build_with(&self, client: &Client, url: Url, method: HttpMethod) -> RequestBuilder {
+        let mut request = match method {
+            HttpMethod::Get => client.get(url),
+            HttpMethod::Post => client.post(url),
+            HttpMethod::Put => client.put(url),
+        };
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request
+    }
+}
+
+/// 按请求配置拉取全部分页，按映射取出记录，汇总后返回
+/// 返回映射后的原始记录，由调用方逐条转换，单条记录格式错误不影响其他记录
+pub async fn fetch_records(
+    api_url: &str,
+    request: &RemoteRequest,
+    mapping: &RemoteMapping,
+) -> AppResult<Vec<Value>> {
+    mapping.validate()?;
+
+    let client = crate::http_client::create_client()?;
+    let base_url = Url::parse(api_url)
+        .map_err(|e| AppError::invalid_input("远端 API 地址无效").with_details(e))?;
+    let max_pages = request.max_pages.unwrap_or(DEFAULT_MAX_PAGES).max(1);
+
+    let mut records = Vec::new();
+    let mut url = first_page_url(&base_url, &request.pagination);
+    let mut visited = HashSet::new();
+
+    for page in 0..max_pages {
+        // 远端返回重复的游标或链接时停止，避免循环请求同一页
+        if !visited.insert(url.to_string()) {
+            log::warn!(page = page; "远端返回了重复的下一页地址，停止翻页");
+            break;
+        }
+
+        let body = fetch_json(request.build(&client, url.clone())).await?;
+        let data = mapping.extract(&body)?;
+        let count = data.len();
+        records.extend(data);
+        log::debug!(page = page, count = count; "已获取远端数据页");
+
+        let next = match &request.pagination {
+            Pagination::None => None,
+            Pagination::Page { param, first_page, page_size, .. } => {
+                let short_page = page_size.is_some_and(|size| count < size as usize);
+                (count > 0 && !short_page)
+                    .then(|| with_query(&url, &[(param, (first_page + page + 1).to_string())]))
+            }
+            Pagination::Cursor { param, cursor_pointer } => pointer_string(&body, cursor_pointer)
+                .map(|cursor| with_query(&base_url, &[(param, cursor)])),
+            Pagination::NextLink { next_pointer } => match pointer_string(&body, next_pointer) {
+                Some(link) => Some(url.join(&link)
+                    .map_err(|e| AppError::parse("远端返回的下一页链接无效", e))?),
+                None => None,
+            },
+        };
+
+        match next {
+            Some(next) => url = next,
+            None => return Ok(records),
+        }
+    }
+
+    log::warn!(max_pages = max_pages; "已达到最大页数，停止翻页");
+    Ok(records)
+}
+
+fn first_page_url(base_url: &Url, pagination: &Pagination) -> Url {
+    match pagination {
+        Pagination::Page { param, first_page, size_param, page_size } => {
+            let mut pairs = vec![(param, first_page.to_string())];
+            if let (Some(size_param), Some(page_size)) = (size_param, page_size) {
+                pairs.push((size_param, page_size.to_string()));
+            }
+            with_query(base_url, &pairs)
+        }
+        _ => base_url.clone(),
+    }
+}
+
+/// 在 URL 上设置查询参数，已有的同名参数会被替换
+fn with_query(base_url: &Url, pairs: &[(&String, String)]) -> Url {
+    let mut url = base_url.clone();
+    let names: HashSet<&str> = pairs.iter().map(|(name, _)| name.as_str()).collect();
+    let kept: Vec<(String, String)> = base_url.query_pairs()
+        .filter(|(name, _)| !names.contains(name.as_ref()))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(kept)
+        .extend_pairs(pairs.iter().map(|(name, value)| (name.as_str(), value.as_str())));
+    url
+}
+
+/// 读取 JSON Pointer 指向的字符串或数字，null 和空字符串视为不存在
+fn pointer_string(body: &Value, pointer: &str) -> Option<String> {
+    match body.pointer(pointer)? {
+        Value::String(text) if !text.is_empty() => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// 发送请求并把响应解析为 JSON
+pub async fn fetch_json(request: RequestBuilder) -> AppResult<Value> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| AppError::network("请求远端 API 失败", e))?;
+
+    let status = response.status();
+    log::debug!(status = status.as_u16(); "远端 API 已响应");
+
+    if !status.is_success() {
+        log::warn!(status = status.as_u16(); "远端 API 返回错误");
+        return Err(AppError::http_status("远端 API 返回错误", status));
+    }
+
+    let response_text = response.text().await
+        .map_err(|e| AppError::network("读取响应体失败", e))?;
+
+    serde_json::from_str(&response_text)
+        .map_err(|e| {
+            log::warn!(body = logging::truncate(&response_text, 500).as_str(); "解析远端 API 响应失败: {}", e);
+            AppError::parse("解析远端 API 响应失败", e)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    type Handler = dyn Fn(&Url) -> Value + Send + Sync;
+
+    /// 启动本地服务端，按请求地址返回 JSON；返回服务地址和已请求的地址（路径加查询参数）
+    async fn serve(handler: Box<Handler>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::from(handler);
+
+        let log = requested.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let (log, handler) = (log.clone(), handler.clone());
+                tokio::spawn(async move {
+                    let mut buffer = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    while !buffer.windows(4).any(|window| window == b"\r\n\r\n") {
+                        let read = socket.read(&mut chunk).await.unwrap();
+                        buffer.extend_from_slice(&chunk[..read]);
+                    }
+
+                    let head = String::from_utf8_lossy(&buffer).into_owned();
+                    let target = head.split_whitespace().nth(1).unwrap().to_string();
+                    log.lock().unwrap().push(target.clone());
+
+                    let url = Url::parse(&format!("http://localhost{}", target)).unwrap();
+                    let payload = handler(&url).to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        payload.len(),
+                        payload
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        (format!("http://{}", address), requested)
+    }
+
+    fn query(url: &Url, name: &str) -> Option<String> {
+        url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned())
+    }
+
+    /// 记录 r0 ~ r{total-1}，按 offset 和 limit 取出一段
+    fn records(total: usize, offset: usize, limit: usize) -> Vec<Value> {
+        (offset..total.min(offset + limit)).map(|index| json!({ "id": format!("r{}", index) })).collect()
+    }
+
+    fn ids(records: &[Value]) -> Vec<String> {
+        records.iter().map(|record| record["id"].as_str().unwrap().to_string()).collect()
+    }
+
+    fn request(pagination: Pagination) -> RemoteRequest {
+        RemoteRequest { pagination, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn page_pagination_stops_on_short_page() {
+        let (base, requested) = serve(Box::new(|url| {
+            let page: usize = query(url, "page").unwrap().parse().unwrap();
+            json!({ "status": 1, "data": records(5, (page - 1) * 2, 2) })
+        })).await;
+
+        let pagination = Pagination::Page {
+            param: "page".to_string(),
+            first_page: 1,
+            size_param: Some("size".to_string()),
+            page_size: Some(2),
+        };
+        let fetched = fetch_records(&format!("{}/tokens?page=9&key=abc", base), &request(pagination), &RemoteMapping::default())
+            .await
+            .unwrap();
+
+        assert_eq!(ids(&fetched), vec!["r0", "r1", "r2", "r3", "r4"]);
+        assert_eq!(*requested.lock().unwrap(), vec![
+            "/tokens?key=abc&page=1&size=2",
+            "/tokens?key=abc&size=2&page=2",
+            "/tokens?key=abc&size=2&page=3",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn page_pagination_without_size_stops_on_empty_page() {
+        let (base, requested) = serve(Box::new(|url| {
+            let page: usize = query(url, "p").unwrap().parse().unwrap();
+            json!(records(3, page * 2, 2))
+        })).await;
+
+        let pagination = Pagination::Page { param: "p".to_string(), first_page: 0, size_param: None, page_size: None };
+        let fetched = fetch_records(&base, &request(pagination), &RemoteMapping::default()).await.unwrap();
+
+        assert_eq!(ids(&fetched), vec!["r0", "r1", "r2"]);
+        assert_eq!(requested.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cursor_pagination_follows_cursor_until_missing() {
+        let (base, requested) = serve(Box::new(|url| {
+            let offset: usize = query(url, "after").map(|cursor| cursor.parse().unwrap()).unwrap_or(0);
+            let next = if offset + 2 < 5 { json!(offset + 2) } else { Value::Null };
+            json!({ "status": 1, "data": records(5, offset, 2), "meta": { "next": next } })
+        })).await;
+
+        let pagination = Pagination::Cursor { param: "after".to_string(), cursor_pointer: "/meta/next".to_string() };
+        let fetched = fetch_records(&format!("{}/sync?scope=all", base), &request(pagination), &RemoteMapping::default())
+            .await
+            .unwrap();
+
+        assert_eq!(fetched.len(), 5);
+        assert_eq!(*requested.lock().unwrap(), vec!["/sync?scope=all", "/sync?scope=all&after=2", "/sync?scope=all&after=4"]);
+    }
+
+    #[tokio::test]
+    async fn next_link_accepts_relative_links() {
+        let (base, requested) = serve(Box::new(|url| match url.path() {
+            "/v1/tokens" => json!({ "status": 1, "data": records(3, 0, 2), "next": "page2?x=1" }),
+            _ => json!({ "status": 1, "data": records(3, 2, 2), "next": "" }),
+        })).await;
+
+        let pagination = Pagination::NextLink { next_pointer: "/next".to_string() };
+        let fetched = fetch_records(&format!("{}/v1/tokens", base), &request(pagination), &RemoteMapping::default())
+            .await
+            .unwrap();
+
+        assert_eq!(ids(&fetched), vec!["r0", "r1", "r2"]);
+        assert_eq!(*requested.lock().unwrap(), vec!["/v1/tokens", "/v1/page2?x=1"]);
+    }
+
+    #[tokio::test]
+    async fn repeated_cursor_and_max_pages_stop_paging() {
+        let (base, requested) = serve(Box::new(|_| json!({ "status": 1, "data": records(1, 0, 1), "next_cursor": "same" }))).await;
+        let pagination = Pagination::Cursor { param: "cursor".to_string(), cursor_pointer: default_cursor_pointer() };
+        let fetched = fetch_records(&base, &request(pagination), &RemoteMapping::default()).await.unwrap();
+        // 第二页的游标与第一页相同，不再请求第三次
+        assert_eq!(fetched.len(), 2);
+        assert_eq!(requested.lock().unwrap().len(), 2);
+
+        let (base, requested) = serve(Box::new(|url| {
+            let page = query(url, "page").unwrap();
+            json!({ "status": 1, "data": [{ "id": page }] })
+        })).await;
+        let pagination = Pagination::Page { param: default_page_param(), first_page: 1, size_param: None, page_size: None };
+        let request = RemoteRequest { max_pages: Some(3), ..request(pagination) };
+        let fetched = fetch_records(&base, &request, &RemoteMapping::default()).await.unwrap();
+        assert_eq!(ids(&fetched), vec!["1", "2", "3"]);
+        assert_eq!(requested.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn with_query_replaces_existing_params() {
+        let base = Url::parse("https://example.com/api?page=5&key=a%20b").unwrap();
+        let page = "page".to_string();
+        let url = with_query(&base, &[(&page, "1".to_string())]);
+        assert_eq!(url.as_str(), "https://example.com/api?key=a+b&page=1");
+    }
+
+    #[test]
+    fn pointer_string_ignores_null_and_empty() {
+        let body = json!({ "a": "x", "b": 7, "c": "", "d": null, "e": true });
+        assert_eq!(pointer_string(&body, "/a").as_deref(), Some("x"));
+        assert_eq!(pointer_string(&body, "/b").as_deref(), Some("7"));
+        assert_eq!(pointer_string(&body, "/c"), None);
+        assert_eq!(pointer_string(&body, "/d"), None);
+        assert_eq!(pointer_string(&body, "/e"), None);
+        assert_eq!(pointer_string(&body, "/missing"), None);
+    }
+}
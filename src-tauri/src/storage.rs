@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, AppResult};
+
+/// 保留的备份数量
+pub const MAX_BACKUPS: usize = 10;
+
+/// 备份文件名后缀
+const BACKUP_SUFFIX: &str = ".bak";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub created_at: String,
+    pub size: u64,
+}
+
+/// 原子写入文件
+/// 先写入同目录下的临时文件并 fsync，再通过 rename 替换目标文件，
+/// 保证目标文件要么是旧内容，要么是完整的新内容
+pub fn atomic_write(path: &Path, content: &[u8]) -> AppResult<()> {
+    atomic_write_with(path, |writer| {
+        writer.write_all(content).map_err(|e| AppError::io("写入临时文件失败", e))
+    })
+}
+
+/// 与 atomic_write 相同，内容由 write 逐步写入带缓冲的临时文件，不需要先在内存中生成完整内容
+pub fn atomic_write_with<F>(path: &Path, write: F) -> AppResult<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> AppResult<()>,
+{
+    let file_name = path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| AppError::invalid_input(format!("无效的文件路径: {}", path.display())))?;
+    let tmp_path = path.with_file_name(format!("{}.tmp", file_name));
+
+    let write_result = (|| -> AppResult<()> {
+        let file = File::create(&tmp_path).map_err(|e| AppError::io("写入临时文件失败", e))?;
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        let file = writer.into_inner().map_err(|e| AppError::io("写入临时文件失败", e.error()))?;
+        file.sync_all().map_err(|e| AppError::io("写入临时文件失败", e))
+    })();
+
+    if let Err(e) = write_result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    fs::rename(&tmp_path, path)
+        .map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            AppError::io("替换文件失败", e)
+        })?;
+
+    sync_parent_dir(path);
+
+    Ok(())
+}
+
+/// 同步父目录，确保 rename 本身落盘（仅 Unix 有效）
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) {
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) {}
+
+/// 备份文件名前缀，例如 tokens.json -> tokens.json.
+fn backup_prefix(path: &Path) -> AppResult<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| format!("{}.", name))
+        .ok_or_else(|| AppError::invalid_input(format!("无效的文件路径: {}", path.display())))
+}
+
+/// 为当前文件创建带时间戳的备份，并清理超出数量的旧备份
+/// 备份与原文件位于同一目录: tokens.json.20240101-120000-000.bak
+pub fn create_backup(path: &Path) -> AppResult<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f");
+    let backup_path = path.with_file_name(format!("{}{}{}", backup_prefix(path)?, timestamp, BACKUP_SUFFIX));
+
+    fs::copy(path, &backup_path)
+        .map_err(|e| AppError::io("创建备份失败", e))?;
+
+    prune_backups(path, MAX_BACKUPS)
+}
+
+/// 列出文件的所有备份，按时间从新到旧排序
+pub fn list_backups(path: &Path) -> AppResult<Vec<BackupInfo>> {
+    let prefix = backup_prefix(path)?;
+    let dir = match path.parent() {
+        Some(dir) if dir.exists() => dir,
+        _ => return Ok(vec![]),
+    };
+
+    let entries = fs::read_dir(dir)
+        .map_err(|e| AppError::io("读取备份目录失败", e))?;
+
+    let mut backups = Vec::new();
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let Some(timestamp) = file_name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(BACKUP_SUFFIX))
+        else {
+            continue;
+        };
+
+        let created_at = match chrono::NaiveDateTime::parse_from_str(timestamp, "%Y%m%d-%H%M%S-%3f") {
+            Ok(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            Err(_) => continue,
+        };
+
+        let size = entry.metadata().map(|meta| meta.len()).unwrap_or(0);
+        backups.push(BackupInfo { file_name, created_at, size });
+    }
+
+    // 时间戳格式固定，按文件名倒序即按时间从新到旧
+    backups.sort_by(|a, b| b.file_name.cmp(&a.file_name));
+
+    Ok(backups)
+}
+
+/// 只保留最新的 keep 个备份
+fn prune_backups(path: &Path, keep: usize) -> AppResult<()> {
+    let backups = list_backups(path)?;
+    for backup in backups.iter().skip(keep) {
+        let _ = fs::remove_file(path.with_file_name(&backup.file_name));
+    }
+    Ok(())
+}
+
+/// 根据备份文件名获取备份路径，拒绝不属于该文件的备份名
+pub fn backup_path(path: &Path, file_name: &str) -> AppResult<PathBuf> {
+    let exists = list_backups(path)?
+        .iter()
+        .any(|backup| backup.file_name == file_name);
+
+    if !exists {
+        return Err(AppError::not_found(format!("未找到备份: {}", file_name)));
+    }
+
+    Ok(path.with_file_name(file_name))
+}
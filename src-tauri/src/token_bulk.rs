@@ -0,0 +1,209 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
+use tauri::State;
+
+use crate::error::{AppError, AppResult};
+use crate::lifecycle::{self, BanStatus};
+use crate::token_manager::{now_timestamp, TokenRecord};
+use crate::token_history::ChangeSource;
+use crate::token_query::{TokenFilter, TokenMatcher};
+use crate::token_store::TokenStore;
+
+/// 批量操作的目标：指定 id 列表，或满足过滤条件的全部记录
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkTarget {
+    Ids(Vec<String>),
+    Filter(Box<TokenFilter>),
+}
+
+/// 部分更新：省略的字段保持不变
+/// 可空字段传 null 表示清空，例如 `{ "tag_name": null }`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TokenPatch {
+    pub tenant_url: Option<String>,
+    /// 手动修改状态，会记录状态变更
+    pub ban_status: Option<BanStatus>,
+    pub skip_check: Option<bool>,
+    #[serde(deserialize_with = "present")]
+    pub portal_url: Option<Option<String>>,
+    #[serde(deserialize_with = "present")]
+    pub email_note: Option<Option<String>>,
+    #[serde(deserialize_with = "present")]
+    pub tag_name: Option<Option<String>>,
+    #[serde(deserialize_with = "present")]
+    pub tag_color: Option<Option<String>>,
+    #[serde(deserialize_with = "present")]
+    pub suspensions: Option<Option<String>>,
+    #[serde(deserialize_with = "present")]
+    pub balance_color_mode: Option<Option<String>>,
+}
+
+/// 字段出现即为 Some，从而区分「未提供」和「显式为 null」
+pub(crate) fn present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl TokenPatch {
+    fn is_empty(&self) -> bool {
+        self.tenant_url.is_none()
+            && self.ban_status.is_none()
+            && self.skip_check.is_none()
+            && self.portal_url.is_none()
+            && self.email_note.is_none()
+            && self.tag_name.is_none()
+            && self.tag_color.is_none()
+            && self.suspensions.is_none()
+            && self.balance_color_mode.is_none()
+    }
+
+    /// 应用到记录上，返回记录是否发生变化
+    fn apply(&self, token: &mut TokenRecord) -> bool {
+        let mut changed = false;
+
+        fn set<T: PartialEq + Clone>(field: &mut T, value: &Option<T>, changed: &mut bool) {
+            if let Some(value) = value {
+                if field != value {
+                    *field = value.clone();
+                    *changed = true;
+                }
+            }
+        }
+
+        set(&mut token.tenant_url, &self.tenant_url, &mut changed);
+        set(&mut token.skip_check, &self.skip_check, &mut changed);
+        set(&mut token.portal_url, &self.portal_url, &mut changed);
+        set(&mut token.email_note, &self.email_note, &mut changed);
+        set(&mut token.tag_name, &self.tag_name, &mut changed);
+        set(&mut token.tag_color, &self.tag_color, &mut changed);
+        set(&mut token.suspensions, &self.suspensions, &mut changed);
+        set(&mut token.balance_color_mode, &self.balance_color_mode, &mut changed);
+
+        if let Some(status) = self.ban_status {
+            changed |= lifecycle::set_status_manually(token, status);
+        }
+
+        changed
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkItemStatus {
+    Updated,
+    /// 记录已是目标值，未写入
+    Unchanged,
+    Deleted,
+    NotFound,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkItemResult {
+    pub id: String,
+    pub status: BulkItemStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkResult {
+    /// 实际更新或删除的记录数
+    pub affected: usize,
+    pub results: Vec<BulkItemResult>,
+}
+
+impl BulkResult {
+    fn new(results: Vec<BulkItemResult>) -> Self {
+        let affected = results.iter()
+            .filter(|result| matches!(result.status, BulkItemStatus::Updated | BulkItemStatus::Deleted))
+            .count();
+        Self { affected, results }
+    }
+}
+
+/// 解析目标记录的 id；按 id 指定时保留输入顺序并去重，过滤条件按存储顺序
+fn resolve_target(tokens: &[TokenRecord], target: &BulkTarget) -> AppResult<Vec<String>> {
+    match target {
+        BulkTarget::Ids(ids) => {
+            let mut seen = HashSet::new();
+            Ok(ids.iter().filter(|id| seen.insert(id.as_str())).cloned().collect())
+        }
+        BulkTarget::Filter(filter) => {
+            let matcher = TokenMatcher::new(filter)?;
+            Ok(tokens.iter().filter(|token| matcher.matches(token)).map(|token| token.id.clone()).collect())
+        }
+    }
+}
+
+/// 批量部分更新，所有修改在一次写盘中完成
+#[tauri::command]
+pub async fn bulk_update_tokens(
+    store: State<'_, TokenStore>,
+    target: BulkTarget,
+    patch: TokenPatch,
+) -> AppResult<BulkResult> {
+    if patch.is_empty() {
+        return Err(AppError::invalid_input("没有需要更新的字段"));
+    }
+
+    let result = store.mutate(ChangeSource::BulkOp, |tokens| {
+        let ids = resolve_target(tokens, &target)?;
+        let updated_at = now_timestamp();
+
+        let results = ids.into_iter()
+            .map(|id| {
+                let status = match tokens.iter_mut().find(|token| token.id == id) {
+                    Some(token) => {
+                        if patch.apply(token) {
+                            token.updated_at = updated_at.clone();
+                            BulkItemStatus::Updated
+                        } else {
+                            BulkItemStatus::Unchanged
+                        }
+                    }
+                    None => BulkItemStatus::NotFound,
+                };
+                BulkItemResult { id, status }
+            })
+            .collect();
+
+        Ok(BulkResult::new(results))
+    }).await?;
+
+    log::info!(affected = result.affected, requested = result.results.len(); "批量更新完成");
+    Ok(result)
+}
+
+/// 批量删除（移入回收站），所有删除在一次写盘中完成
+#[tauri::command]
+pub async fn bulk_delete_tokens(store: State<'_, TokenStore>, target: BulkTarget) -> AppResult<BulkResult> {
+    let result = store.mutate(ChangeSource::BulkOp, |tokens| {
+        let ids = resolve_target(tokens, &target)?;
+        let existing: HashSet<&str> = tokens.iter().map(|token| token.id.as_str()).collect();
+
+        let results: Vec<BulkItemResult> = ids.into_iter()
+            .map(|id| {
+                let status = if existing.contains(id.as_str()) {
+                    BulkItemStatus::Deleted
+                } else {
+                    BulkItemStatus::NotFound
+                };
+                BulkItemResult { id, status }
+            })
+            .collect();
+
+        let deleted: HashSet<&str> = results.iter()
+            .filter(|result| result.status == BulkItemStatus::Deleted)
+            .map(|result| result.id.as_str())
+            .collect();
+        tokens.retain(|token| !deleted.contains(token.id.as_str()));
+
+        Ok(BulkResult::new(results))
+    }).await?;
+
+    log::info!(affected = result.affected, requested = result.results.len(); "批量删除完成");
+    Ok(result)
+}
@@ -0,0 +1,386 @@
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::{AppError, AppResult};
+use crate::token_manager::TokenRecord;
+use crate::token_query::TokenFilter;
+use crate::token_schema::StoreData;
+use crate::tombstone::Tombstone;
+use crate::trash::TrashedToken;
+
+/// 数据库结构版本（PRAGMA user_version）
+/// - v1: tokens 表
+/// - v2: 新增 trash 表（回收站）
+/// - v3: 新增 tombstones 表（删除标记）
+pub const DB_SCHEMA_VERSION: u32 = 3;
+
+/// 完整记录以 JSON 保存在 data 列中，常用查询字段单独成列并建立索引，
+/// 这样 TokenRecord 增加字段时不需要修改表结构
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS tokens (
+    id TEXT PRIMARY KEY NOT NULL,
+    auth_session TEXT NOT NULL,
+    email_note TEXT,
+    tenant_url TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_tokens_auth_session ON tokens(auth_session);
+CREATE INDEX IF NOT EXISTS idx_tokens_email_note ON tokens(email_note);
+CREATE INDEX IF NOT EXISTS idx_tokens_tenant_url ON tokens(tenant_url);
+CREATE TABLE IF NOT EXISTS trash (
+    id TEXT PRIMARY KEY NOT NULL,
+    deleted_at TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tombstones (
+    id TEXT PRIMARY KEY NOT NULL,
+    session_hash TEXT NOT NULL,
+    deleted_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_tombstones_session_hash ON tombstones(session_hash);
+";
+
+/// 一次事务中写入的变更
+pub struct Changes<'a> {
+    pub upserts: Vec<&'a TokenRecord>,
+    pub deletes: Vec<&'a str>,
+    pub trash_upserts: Vec<&'a TrashedToken>,
+    pub trash_deletes: Vec<&'a str>,
+    pub tombstone_upserts: Vec<&'a Tombstone>,
+    pub tombstone_deletes: Vec<&'a str>,
+}
+
+impl Changes<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.upserts.is_empty()
+            && self.deletes.is_empty()
+            && self.trash_upserts.is_empty()
+            && self.trash_deletes.is_empty()
+            && self.tombstone_upserts.is_empty()
+            && self.tombstone_deletes.is_empty()
+    }
+}
+
+/// 获取 tokens.db 文件路径（与 tokens.json 位于同一目录）
+fn get_db_path() -> AppResult<PathBuf> {
+    Ok(crate::token_manager::get_tokens_file_path()?.with_file_name("tokens.db"))
+}
+
+/// 打开数据库；首次打开时建表并从 tokens.json 迁移数据，旧版本数据库补建新增的表
+pub fn open() -> AppResult<Connection> {
+    let db_path = get_db_path()?;
+    let mut conn = Connection::open(&db_path)
+        .map_err(|e| AppError::database("打开 tokens.db 失败", e))?;
+
+    match schema_version(&conn)? {
+        0 => migrate_from_json(&mut conn)?,
+        version if version < DB_SCHEMA_VERSION => upgrade(&mut conn)?,
+        version if version > DB_SCHEMA_VERSION => {
+            return Err(AppError::invalid_state(format!(
+                "tokens.db 版本 ({}) 高于当前支持的版本 ({})，请升级应用",
+                version, DB_SCHEMA_VERSION
+            )));
+        }
+        _ => {}
+    }
+
+    Ok(conn)
+}
+
+/// 升级旧版本数据库：建表语句均为 IF NOT EXISTS，重复执行只会补建缺失的表
+fn upgrade(conn: &mut Connection) -> AppResult<()> {
+    let tx = conn.transaction()
+        .map_err(|e| AppError::database("开启事务失败", e))?;
+    tx.execute_batch(SCHEMA)
+        .map_err(|e| AppError::database("升级数据表失败", e))?;
+    tx.pragma_update(None, "user_version", DB_SCHEMA_VERSION)
+        .map_err(|e| AppError::database("写入数据库版本失败", e))?;
+    tx.commit()
+        .map_err(|e| AppError::database("提交升级失败", e))
+}
+
+/// 一次性迁移：建表、导入 tokens.json 的全部记录，成功后将原文件重命名为 tokens.json.migrated
+fn migrate_from_json(conn: &mut Connection) -> AppResult<()> {
+    let json_path = crate::token_manager::get_tokens_file_path()?;
+    let data = if json_path.exists() {
+        let content = fs::read_to_string(&json_path)
+            .map_err(|e| AppError::io("读取 tokens.json 失败", e))?;
+        if crate::vault::VaultFile::parse(&content).is_some() {
+            return Err(AppError::invalid_state("已启用保险库，SQLite 存储不支持加密，请改回 JSON 存储"));
+        }
+        crate::token_schema::load(&content)?.1
+    } else {
+        StoreData::default()
+    };
+
+    let tx = conn.transaction()
+        .map_err(|e| AppError::database("开启事务失败", e))?;
+    tx.execute_batch(SCHEMA)
+        .map_err(|e| AppError::database("创建数据表失败", e))?;
+    for token in &data.tokens {
+        insert(&tx, token)?;
+    }
+    for trashed in &data.trash {
+        upsert_trash(&tx, trashed)?;
+    }
+    for tombstone in &data.tombstones {
+        upsert_tombstone(&tx, tombstone)?;
+    }
+    tx.pragma_update(None, "user_version", DB_SCHEMA_VERSION)
+        .map_err(|e| AppError::database("写入数据库版本失败", e))?;
+    tx.commit()
+        .map_err(|e| AppError::database("提交迁移失败", e))?;
+
+    if json_path.exists() {
+        fs::rename(&json_path, json_path.with_file_name("tokens.json.migrated"))
+            .map_err(|e| AppError::io("重命名 tokens.json 失败", e))?;
+    }
+
+    log::info!(count = data.tokens.len(); "已从 tokens.json 迁移记录到 tokens.db");
+
+    Ok(())
+}
+
+/// 反向迁移：切换回 JSON 存储且 tokens.json 不存在时，把 tokens.db 的全部数据交给 write 写入 tokens.json，
+/// 成功后将数据库重命名为 tokens.db.migrated，之后再切换到 SQLite 时会重新从 tokens.json 迁移
+/// 没有数据库文件时返回 None
+pub fn migrate_to_json(write: impl FnOnce(&StoreData) -> AppResult<()>) -> AppResult<Option<StoreData>> {
+    let db_path = get_db_path()?;
+    if !db_path.exists() {
+        return Ok(None);
+    }
+
+    // 重命名前关闭连接（Windows 下无法重命名已打开的文件）
+    let data = {
+        let conn = open()?;
+        StoreData {
+            tokens: load_all(&conn)?,
+            trash: load_trash(&conn)?,
+            tombstones: load_tombstones(&conn)?,
+        }
+    };
+
+    write(&data)?;
+    fs::rename(&db_path, db_path.with_file_name("tokens.db.migrated"))
+        .map_err(|e| AppError::io("重命名 tokens.db 失败", e))?;
+
+    log::info!(count = data.tokens.len(); "已从 tokens.db 迁移记录到 tokens.json");
+
+    Ok(Some(data))
+}
+
+/// 读取数据库结构版本（0 表示尚未初始化）
+pub fn schema_version(conn: &Connection) -> AppResult<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| AppError::database("读取数据库版本失败", e))
+}
+
+fn parse_record(data: String) -> AppResult<TokenRecord> {
+    serde_json::from_str(&data)
+        .map_err(|e| AppError::parse("解析数据库记录失败", e))
+}
+
+/// 按插入顺序读取所有记录
+pub fn load_all(conn: &Connection) -> AppResult<Vec<TokenRecord>> {
+    let mut stmt = conn.prepare("SELECT data FROM tokens ORDER BY rowid")
+        .map_err(|e| AppError::database("查询 tokens 失败", e))?;
+
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| AppError::database("查询 tokens 失败", e))?;
+
+    let mut tokens = Vec::new();
+    for data in rows {
+        let data = data.map_err(|e| AppError::database("读取记录失败", e))?;
+        tokens.push(parse_record(data)?);
+    }
+
+    Ok(tokens)
+}
+
+/// 按 id 读取单条记录（主键查询）
+pub fn find_by_id(conn: &Connection, id: &str) -> AppResult<Option<TokenRecord>> {
+    find_one(conn, "SELECT data FROM tokens WHERE id = ?1", id)
+}
+
+/// 按 auth_session 读取单条记录（使用 idx_tokens_auth_session 索引）
+pub fn find_by_session(conn: &Connection, session: &str) -> AppResult<Option<TokenRecord>> {
+    find_one(conn, "SELECT data FROM tokens WHERE auth_session = ?1 ORDER BY rowid LIMIT 1", session)
+}
+
+fn find_one(conn: &Connection, sql: &str, value: &str) -> AppResult<Option<TokenRecord>> {
+    conn.query_row(sql, params![value], |row| row.get::<_, String>(0))
+        .optional()
+        .map_err(|e| AppError::database("查询记录失败", e))?
+        .map(parse_record)
+        .transpose()
+}
+
+/// 用 SQL 预先筛选可能满足过滤条件的记录（按插入顺序）
+/// 只下推能用索引或不依赖解析的条件：tenant_url、邮箱子串和标签；
+/// 返回的是候选集，其余条件（时间、积分、状态等）仍由调用方在内存中判断
+pub fn query_candidates(conn: &Connection, filter: &TokenFilter) -> AppResult<Vec<TokenRecord>> {
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+
+    if let Some(tenant_url) = &filter.tenant_url {
+        conditions.push("tenant_url = ?");
+        values.push(tenant_url.clone());
+    }
+    // SQLite 的 LIKE 只对 ASCII 字符忽略大小写，非 ASCII 的子串交给内存中判断
+    if let Some(email) = filter.email.as_deref().map(str::trim).filter(|email| !email.is_empty() && email.is_ascii()) {
+        conditions.push("email_note LIKE ? ESCAPE '\\'");
+        values.push(format!("%{}%", escape_like(email)));
+    }
+    if let Some(tag_name) = &filter.tag_name {
+        conditions.push("json_extract(data, '$.tag_name') = ?");
+        values.push(tag_name.clone());
+    }
+
+    let mut sql = String::from("SELECT data FROM tokens");
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY rowid");
+
+    let mut stmt = conn.prepare(&sql)
+        .map_err(|e| AppError::database("查询 tokens 失败", e))?;
+
+    let rows = stmt.query_map(params_from_iter(values), |row| row.get::<_, String>(0))
+        .map_err(|e| AppError::database("查询 tokens 失败", e))?;
+
+    let mut tokens = Vec::new();
+    for data in rows {
+        let data = data.map_err(|e| AppError::database("读取记录失败", e))?;
+        tokens.push(parse_record(data)?);
+    }
+
+    Ok(tokens)
+}
+
+/// 转义 LIKE 模式中的通配符
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// 按删除顺序读取回收站
+pub fn load_trash(conn: &Connection) -> AppResult<Vec<TrashedToken>> {
+    let mut stmt = conn.prepare("SELECT deleted_at, data FROM trash ORDER BY rowid")
+        .map_err(|e| AppError::database("查询回收站失败", e))?;
+
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| AppError::database("查询回收站失败", e))?;
+
+    let mut trash = Vec::new();
+    for row in rows {
+        let (deleted_at, data) = row.map_err(|e| AppError::database("读取回收站记录失败", e))?;
+        trash.push(TrashedToken { deleted_at, token: parse_record(data)? });
+    }
+
+    Ok(trash)
+}
+
+/// 按删除顺序读取删除标记
+pub fn load_tombstones(conn: &Connection) -> AppResult<Vec<Tombstone>> {
+    let mut stmt = conn.prepare("SELECT id, session_hash, deleted_at FROM tombstones ORDER BY rowid")
+        .map_err(|e| AppError::database("查询删除标记失败", e))?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok(Tombstone {
+            id: row.get(0)?,
+            session_hash: row.get(1)?,
+            deleted_at: row.get(2)?,
+        })
+    })
+    .map_err(|e| AppError::database("查询删除标记失败", e))?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| AppError::database("读取删除标记失败", e))
+}
+
+/// 写入删除标记（同一 id 覆盖）
+fn upsert_tombstone(conn: &Connection, tombstone: &Tombstone) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO tombstones (id, session_hash, deleted_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(id) DO UPDATE SET session_hash = ?2, deleted_at = ?3",
+        params![tombstone.id, tombstone.session_hash, tombstone.deleted_at],
+    )
+    .map_err(|e| AppError::database("写入删除标记失败", e))?;
+
+    Ok(())
+}
+
+/// 写入回收站条目（同一 id 覆盖）
+fn upsert_trash(conn: &Connection, trashed: &TrashedToken) -> AppResult<()> {
+    let data = serde_json::to_string(&trashed.token)
+        .map_err(|e| AppError::parse("序列化记录失败", e))?;
+
+    conn.execute(
+        "INSERT INTO trash (id, deleted_at, data) VALUES (?1, ?2, ?3)
+         ON CONFLICT(id) DO UPDATE SET deleted_at = ?2, data = ?3",
+        params![trashed.token.id, trashed.deleted_at, data],
+    )
+    .map_err(|e| AppError::database("写入回收站失败", e))?;
+
+    Ok(())
+}
+
+/// 插入单条记录
+pub fn insert(conn: &Connection, token: &TokenRecord) -> AppResult<()> {
+    let data = serde_json::to_string(token)
+        .map_err(|e| AppError::parse("序列化记录失败", e))?;
+
+    conn.execute(
+        "INSERT INTO tokens (id, auth_session, email_note, tenant_url, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![token.id, token.auth_session, token.email_note, token.tenant_url, data],
+    )
+    .map_err(|e| AppError::database("插入记录失败", e))?;
+
+    Ok(())
+}
+
+/// 在一个事务中写入新增/修改的记录并删除指定 id 的记录，回收站和删除标记同理
+pub fn apply_changes(conn: &mut Connection, changes: &Changes) -> AppResult<()> {
+    let tx = conn.transaction()
+        .map_err(|e| AppError::database("开启事务失败", e))?;
+
+    for id in &changes.tombstone_deletes {
+        tx.execute("DELETE FROM tombstones WHERE id = ?1", params![id])
+            .map_err(|e| AppError::database("删除删除标记失败", e))?;
+    }
+
+    for tombstone in &changes.tombstone_upserts {
+        upsert_tombstone(&tx, tombstone)?;
+    }
+
+    for id in &changes.trash_deletes {
+        tx.execute("DELETE FROM trash WHERE id = ?1", params![id])
+            .map_err(|e| AppError::database("删除回收站记录失败", e))?;
+    }
+
+    for trashed in &changes.trash_upserts {
+        upsert_trash(&tx, trashed)?;
+    }
+
+    for id in &changes.deletes {
+        tx.execute("DELETE FROM tokens WHERE id = ?1", params![id])
+            .map_err(|e| AppError::database("删除记录失败", e))?;
+    }
+
+    for token in &changes.upserts {
+        let data = serde_json::to_string(token)
+            .map_err(|e| AppError::parse("序列化记录失败", e))?;
+
+        // 使用 ON CONFLICT 更新而不是 REPLACE，保留原记录的 rowid（即排列顺序）
+        tx.execute(
+            "INSERT INTO tokens (id, auth_session, email_note, tenant_url, data) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(id) DO UPDATE SET auth_session = ?2, email_note = ?3, tenant_url = ?4, data = ?5",
+            params![token.id, token.auth_session, token.email_note, token.tenant_url, data],
+        )
+        .map_err(|e| AppError::database("写入记录失败", e))?;
+    }
+
+    tx.commit()
+        .map_err(|e| AppError::database("提交事务失败", e))
+}
@@ -0,0 +1,593 @@
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::error::{self, AppError, AppResult};
+use crate::remote_mapping::RemoteMapping;
+use crate::storage;
+use crate::token_history::ChangeSource;
+use crate::token_import::{self, MergeStrategy};
+use crate::token_manager::{ImportResult, RemoteTokenRecord, TokenRecord};
+use crate::token_query::{self, TokenFilter};
+use crate::token_store::TokenStore;
+
+/// CSV 可以导出的列，portal_info 的子字段以点号连接；默认导出全部
+const CSV_COLUMNS: &[&str] = &[
+    "id",
+    "email_note",
+    "tenant_url",
+    "access_token",
+    "auth_session",
+    "ban_status",
+    "portal_info.credits_balance",
+    "portal_info.expiry_date",
+    "portal_url",
+    "tag_name",
+    "tag_color",
+    "suspensions",
+    "skip_check",
+    "balance_color_mode",
+    "created_at",
+    "updated_at",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// 格式化的 JSON 数组
+    Json,
+    Csv,
+    /// 每行一条 JSON 记录
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportResult {
+    pub path: String,
+    pub count: usize,
+}
+
+/// 弹出保存对话框，用户取消时返回 None
+async fn pick_save_path(app: &AppHandle, format: ExportFormat) -> Option<PathBuf> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .set_title("导出 Token")
+        .set_file_name(format!("tokens.{}", format.extension()))
+        .add_filter(format.extension().to_uppercase(), &[format.extension()])
+        .save_file(move |path| {
+            let _ = tx.send(path);
+        });
+    rx.await.ok().flatten().and_then(|path| path.into_path().ok())
+}
+
+/// 弹出打开对话框，用户取消时返回 None
+async fn pick_open_path(app: &AppHandle) -> Option<PathBuf> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .set_title("导入 Token")
+        .add_filter("Token 文件", &["json", "csv", "ndjson", "jsonl"])
+        .pick_file(move |path| {
+            let _ = tx.send(path);
+        });
+    rx.await.ok().flatten().and_then(|path| path.into_path().ok())
+}
+
+/// CSV 字段转义：包含分隔符、引号或换行时加引号
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_cell(record: &Value, column: &str) -> String {
+    let pointer: String = column.split('.').map(|segment| format!("/{}", segment)).collect();
+    match record.pointer(&pointer) {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// 导出时逐条转换记录，不需要先生成全部记录的副本
+struct ExportRecord<'a>(&'a TokenRecord);
+
+impl Serialize for ExportRecord<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.to_remote_value()
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+fn write_error(e: serde_json::Error) -> AppError {
+    if e.is_io() {
+        AppError::io("写入文件失败", e)
+    } else {
+        AppError::parse("序列化记录失败", e)
+    }
+}
+
+/// 按格式逐条写入记录，结构与导入时接受的 RemoteTokenRecord 一致
+fn write_records(writer: &mut impl Write, tokens: &[TokenRecord], format: ExportFormat, columns: &[String]) -> AppResult<()> {
+    let line_error = |e| AppError::io("写入文件失败", e);
+
+    match format {
+        ExportFormat::Json => {
+            let mut serializer = serde_json::Serializer::pretty(&mut *writer);
+            serializer.collect_seq(tokens.iter().map(ExportRecord)).map_err(write_error)?;
+        }
+        ExportFormat::Ndjson => {
+            for token in tokens {
+                serde_json::to_writer(&mut *writer, &ExportRecord(token)).map_err(write_error)?;
+                writer.write_all(b"\n").map_err(line_error)?;
+            }
+        }
+        ExportFormat::Csv => {
+            let header: Vec<String> = columns.iter().map(|column| csv_escape(column)).collect();
+            writeln!(writer, "{}", header.join(",")).map_err(line_error)?;
+            for token in tokens {
+                let record = token.to_remote_value()?;
+                let row: Vec<String> = columns.iter()
+                    .map(|column| csv_escape(&csv_cell(&record, column)))
+                    .collect();
+                writeln!(writer, "{}", row.join(",")).map_err(line_error)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 导出记录到文件
+/// filter 为空时导出全部；columns 只对 CSV 生效，默认导出全部列；path 为空时弹出保存对话框，用户取消时返回 None
+#[tauri::command]
+pub async fn export_tokens(
+    app: AppHandle,
+    store: State<'_, TokenStore>,
+    format: ExportFormat,
+    filter: Option<TokenFilter>,
+    columns: Option<Vec<String>>,
+    path: Option<String>,
+) -> AppResult<Option<ExportResult>> {
+    let columns = columns
+        .filter(|columns| !columns.is_empty())
+        .unwrap_or_else(|| CSV_COLUMNS.iter().map(|column| column.to_string()).collect());
+    let unknown: Vec<&str> = columns.iter()
+        .map(String::as_str)
+        .filter(|column| !CSV_COLUMNS.contains(column))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(AppError::invalid_input("包含未知的导出列").with_details(unknown.join(", ")));
+    }
+
+    let tokens = store.list().await?;
+    let tokens = match &filter {
+        Some(filter) => token_query::filter_tokens(tokens, filter)?,
+        None => tokens,
+    };
+
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => match pick_save_path(&app, format).await {
+            Some(path) => path,
+            None => return Ok(None),
+        },
+    };
+
+    let count = tokens.len();
+    let target = path.clone();
+    error::spawn_blocking(move || {
+        storage::atomic_write_with(&target, |writer| write_records(writer, &tokens, format, &columns))
+    }).await?;
+
+    log::info!(path = path.display().to_string().as_str(), count = count, format = format.extension(); "已导出记录");
+
+    Ok(Some(ExportResult {
+        path: path.display().to_string(),
+        count,
+    }))
+}
+
+/// 逐条读取 JSON 数组中的元素，不需要把整个数组读入内存
+struct ArrayVisitor<'a, F: FnMut(Value)> {
+    on_record: &'a mut F,
+}
+
+impl<'de, F: FnMut(Value)> Visitor<'de> for ArrayVisitor<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("记录数组")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(record) = seq.next_element::<Value>()? {
+            (self.on_record)(record);
+        }
+        Ok(())
+    }
+}
+
+fn read_json_array(reader: impl Read, on_record: &mut impl FnMut(Value)) -> AppResult<()> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    deserializer.deserialize_seq(ArrayVisitor { on_record })
+        .and_then(|_| deserializer.end())
+        .map_err(|e| AppError::parse("解析 JSON 文件失败", e))
+}
+
+/// 读取一条 CSV 记录，引号内的换行属于字段内容；文件结束时返回 None
+fn read_csv_record(reader: &mut impl BufRead) -> io::Result<Option<Vec<String>>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            if fields.is_empty() && field.is_empty() && !in_quotes {
+                return Ok(None);
+            }
+            fields.push(field);
+            return Ok(Some(fields));
+        }
+
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (in_quotes, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                (true, '"') => in_quotes = false,
+                (true, c) => field.push(c),
+                (false, '"') => in_quotes = true,
+                (false, ',') => fields.push(std::mem::take(&mut field)),
+                (false, '\r' | '\n') => {}
+                (false, c) => field.push(c),
+            }
+        }
+
+        if !in_quotes {
+            fields.push(field);
+            return Ok(Some(fields));
+        }
+    }
+}
+
+/// 把 CSV 的一行转换为记录对象：空单元格视为缺失，点号列名写入嵌套对象
+fn csv_row_to_value(headers: &[String], row: Vec<String>) -> Value {
+    let mut record = Map::new();
+    for (header, cell) in headers.iter().zip(row) {
+        if cell.is_empty() {
+            continue;
+        }
+
+        let value = match header.as_str() {
+            "portal_info.credits_balance" => cell.trim().parse::<i64>().map(Value::from).unwrap_or(Value::String(cell)),
+            "skip_check" => match cell.trim().to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" => Value::Bool(true),
+                "false" | "0" | "no" => Value::Bool(false),
+                _ => Value::String(cell),
+            },
+            _ => Value::String(cell),
+        };
+
+        match header.split_once('.') {
+            Some((parent, child)) => {
+                let parent = record.entry(parent).or_insert_with(|| Value::Object(Map::new()));
+                if let Value::Object(parent) = parent {
+                    parent.insert(child.to_string(), value);
+                }
+            }
+            None => {
+                record.insert(header.clone(), value);
+            }
+        }
+    }
+    Value::Object(record)
+}
+
+fn read_csv(mut reader: impl BufRead, on_record: &mut impl FnMut(Value)) -> AppResult<()> {
+    let headers: Vec<String> = match read_csv_record(&mut reader).map_err(|e| AppError::io("读取 CSV 文件失败", e))? {
+        Some(headers) => headers.into_iter().map(|header| header.trim().to_string()).collect(),
+        None => return Ok(()),
+    };
+
+    while let Some(row) = read_csv_record(&mut reader).map_err(|e| AppError::io("读取 CSV 文件失败", e))? {
+        // 跳过空行
+        if row.len() == 1 && row[0].trim().is_empty() {
+            continue;
+        }
+        on_record(csv_row_to_value(&headers, row));
+    }
+    Ok(())
+}
+
+fn read_ndjson(reader: impl BufRead, on_record: &mut impl FnMut(Value)) -> AppResult<()> {
+    for line in reader.lines() {
+        let line = line.map_err(|e| AppError::io("读取 NDJSON 文件失败", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // 无法解析的行作为一条无效记录交给调用方统计
+        on_record(serde_json::from_str(&line).unwrap_or(Value::String(line)));
+    }
+    Ok(())
+}
+
+/// 按 DeserializeSeed 逐条读取记录数组，供对象中的 tokens / data 字段使用
+struct RecordsSeed<'a, F: FnMut(Value)> {
+    on_record: &'a mut F,
+}
+
+impl<'de, F: FnMut(Value)> DeserializeSeed<'de> for RecordsSeed<'_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(ArrayVisitor { on_record: self.on_record })
+    }
+}
+
+/// 在对象中找到第一个 tokens（本应用的存储文件）或 data（远端 API 格式）字段并逐条读取，其余字段直接跳过
+/// 返回是否找到了记录数组
+struct ObjectVisitor<'a, F: FnMut(Value)> {
+    on_record: &'a mut F,
+}
+
+impl<'de, F: FnMut(Value)> Visitor<'de> for ObjectVisitor<'_, F> {
+    type Value = bool;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("包含 tokens 或 data 字段的对象")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<bool, A::Error> {
+        let mut found = false;
+        while let Some(key) = map.next_key::<String>()? {
+            if !found && (key == "tokens" || key == "data") {
+                map.next_value_seed(RecordsSeed { on_record: &mut *self.on_record })?;
+                found = true;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(found)
+    }
+}
+
+fn read_json_object(reader: impl Read, on_record: &mut impl FnMut(Value)) -> AppResult<()> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let found = deserializer.deserialize_map(ObjectVisitor { on_record })
+        .and_then(|found| deserializer.end().map(|_| found))
+        .map_err(|e| AppError::parse("解析 JSON 文件失败", e))?;
+
+    if !found {
+        return Err(AppError::parse("文件中未找到记录数组", "需要 tokens 或 data 字段"));
+    }
+    Ok(())
+}
+
+/// 以对象开头的文件：首行是不含记录数组的完整 JSON 对象时按 NDJSON 处理，
+/// 否则按对象逐条读取 tokens 或 data 中的记录
+fn read_object_file(mut reader: impl BufRead, on_record: &mut impl FnMut(Value)) -> AppResult<()> {
+    let mut first_line = String::new();
+    reader.read_line(&mut first_line).map_err(|e| AppError::io("读取文件失败", e))?;
+
+    if let Ok(Value::Object(object)) = serde_json::from_str::<Value>(&first_line) {
+        let is_envelope = ["tokens", "data"].iter().any(|key| object.get(*key).is_some_and(Value::is_array));
+        if !is_envelope {
+            return read_ndjson(io::Cursor::new(first_line).chain(reader), on_record);
+        }
+    }
+
+    read_json_object(io::Cursor::new(first_line).chain(reader), on_record)
+}
+
+/// 根据首个非空白字符识别格式并逐条读取记录：[ 为 JSON 数组，{ 为 NDJSON 或 JSON 对象，其余按 CSV 处理
+fn read_records(path: &Path, on_record: &mut impl FnMut(Value)) -> AppResult<()> {
+    let file = File::open(path).map_err(|e| AppError::io("打开文件失败", e))?;
+    let mut reader = BufReader::new(file);
+
+    // 跳过 UTF-8 BOM 和开头的空白
+    let first = loop {
+        let buffer = reader.fill_buf().map_err(|e| AppError::io("读取文件失败", e))?;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        if buffer.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            reader.consume(3);
+            continue;
+        }
+        match buffer.iter().position(|byte| !byte.is_ascii_whitespace()) {
+            Some(position) => {
+                let first = buffer[position];
+                reader.consume(position);
+                break first;
+            }
+            None => {
+                let len = buffer.len();
+                reader.consume(len);
+            }
+        }
+    };
+
+    match first {
+        b'[' => read_json_array(reader, on_record),
+        b'{' => read_object_file(reader, on_record),
+        _ => read_csv(reader, on_record),
+    }
+}
+
+/// 从文件导入记录，自动识别 JSON、NDJSON 和 CSV，字段规则与远端导入相同
+/// path 为空时弹出打开对话框，用户取消时返回 None
+#[tauri::command]
+pub async fn import_tokens_from_file(
+    app: AppHandle,
+    store: State<'_, TokenStore>,
+    path: Option<String>,
+    strategy: Option<MergeStrategy>,
+    generate_missing: Option<bool>,
+) -> AppResult<Option<ImportResult>> {
+    let strategy = strategy.unwrap_or_default();
+    let generate_missing = generate_missing.unwrap_or(false);
+
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => match pick_open_path(&app).await {
+            Some(path) => path,
+            None => return Ok(None),
+        },
+    };
+
+    let source = path.clone();
+    let (converted, errors, total) = error::spawn_blocking(move || {
+        let mut converted = Vec::new();
+        let mut errors = Vec::new();
+        let mut index = 0;
+        read_records(&source, &mut |value| {
+            let value = RemoteMapping::default().map_record(&value);
+            match RemoteTokenRecord::convert(&value, index, generate_missing) {
+                Ok(token) => converted.push(token),
+                Err(e) => {
+                    log::warn!(index = index; "文件记录转换失败: {}", e);
+                    errors.push(e);
+                }
+            }
+            index += 1;
+        })?;
+        Ok((converted, errors, index))
+    }).await?;
+
+    let result = store.mutate_data(ChangeSource::FileImport, |data| {
+        let actions = token_import::merge_into(&mut data.tokens, &data.tombstones, converted, strategy);
+        Ok(ImportResult::from_actions(&actions, errors))
+    }).await?;
+
+    log::info!(
+        path = path.display().to_string().as_str(),
+        total = total,
+        imported = result.imported,
+        updated = result.updated,
+        skipped = result.skipped,
+        failed = result.failed;
+        "文件导入完成"
+    );
+
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn read(content: &str) -> AppResult<Vec<Value>> {
+        let path = std::env::temp_dir().join(format!("aug-session-sync-{}-{}", std::process::id(), crate::token_manager::generate_id()));
+        std::fs::write(&path, content).unwrap();
+        let mut records = Vec::new();
+        let result = read_records(&path, &mut |value| records.push(value));
+        let _ = std::fs::remove_file(&path);
+        result.map(|_| records)
+    }
+
+    fn token(id: &str, note: &str) -> TokenRecord {
+        TokenRecord {
+            email_note: Some(note.to_string()),
+            ..TokenRecord::test(id, &format!("session-{}", id))
+        }
+    }
+
+    #[test]
+    fn reads_json_array() {
+        let records = read("\u{feff}  [{\"id\": \"a\"}, {\"id\": \"b\"}]").unwrap();
+        assert_eq!(records, vec![json!({ "id": "a" }), json!({ "id": "b" })]);
+    }
+
+    #[test]
+    fn reads_records_from_pretty_object() {
+        let content = serde_json::to_string_pretty(&json!({
+            "schema_version": 3,
+            "trash": [{ "id": "ignored" }],
+            "tokens": [{ "id": "a" }, { "id": "b" }],
+        })).unwrap();
+        let records = read(&content).unwrap();
+        assert_eq!(records, vec![json!({ "id": "a" }), json!({ "id": "b" })]);
+    }
+
+    #[test]
+    fn reads_records_from_single_line_envelope() {
+        let records = read(r#"{"status":1,"data":[{"id":"a"}]}"#).unwrap();
+        assert_eq!(records, vec![json!({ "id": "a" })]);
+    }
+
+    #[test]
+    fn object_without_records_is_an_error() {
+        let error = read("{\n  \"items\": []\n}").unwrap_err();
+        assert_eq!(error.code, crate::error::ErrorCode::Parse);
+    }
+
+    #[test]
+    fn reads_ndjson_and_keeps_invalid_lines() {
+        let records = read("{\"id\":\"a\"}\n\n{\"id\":\"b\"}\nnot json\n").unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1], json!({ "id": "b" }));
+        assert_eq!(records[2], json!("not json"));
+    }
+
+    #[test]
+    fn csv_quoted_fields_may_span_lines() {
+        let records = read("id,email_note,portal_info.credits_balance,skip_check\r\na,\"line 1\nline 2, \"\"quoted\"\"\",42,yes\r\n\r\nb,,,\n").unwrap();
+        assert_eq!(records, vec![
+            json!({ "id": "a", "email_note": "line 1\nline 2, \"quoted\"", "portal_info": { "credits_balance": 42 }, "skip_check": true }),
+            json!({ "id": "b" }),
+        ]);
+    }
+
+    #[test]
+    fn export_round_trips_through_import() {
+        let tokens = vec![token("a", "note, with \"comma\"\nand newline"), token("b", "plain")];
+        let columns: Vec<String> = CSV_COLUMNS.iter().map(|column| column.to_string()).collect();
+
+        for format in [ExportFormat::Json, ExportFormat::Ndjson, ExportFormat::Csv] {
+            let mut content = Vec::new();
+            write_records(&mut content, &tokens, format, &columns).unwrap();
+
+            let imported: Vec<TokenRecord> = read(&String::from_utf8(content).unwrap()).unwrap()
+                .iter()
+                .enumerate()
+                .map(|(index, value)| RemoteTokenRecord::convert(value, index, false).unwrap())
+                .collect();
+            assert_eq!(imported, tokens, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn json_export_matches_pretty_array() {
+        let tokens = vec![token("a", "a")];
+        let mut content = Vec::new();
+        write_records(&mut content, &tokens, ExportFormat::Json, &[]).unwrap();
+
+        let expected = serde_json::to_string_pretty(&vec![tokens[0].to_remote_value().unwrap()]).unwrap();
+        assert_eq!(String::from_utf8(content).unwrap(), expected);
+    }
+}
@@ -0,0 +1,346 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::error::{AppError, AppResult};
+use crate::logging;
+use crate::token_manager::{now_timestamp, TokenRecord};
+
+/// 历史中只记录遮蔽后的值
+const SECRET_FIELDS: &[&str] = &["auth_session", "access_token"];
+
+/// 保险库不加密历史文件，启用后这些字段同样只记录遮蔽后的值
+const VAULT_MASKED_FIELDS: &[&str] = &["email_note"];
+
+/// 不计入变更的字段：id 为记录标识，其余为每次写入都会变化的派生信息
+const IGNORED_FIELDS: &[&str] = &["id", "updated_at", "last_check", "status_transitions"];
+
+/// 变更来源
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeSource {
+    /// 界面中的新增、编辑、删除
+    Manual,
+    /// 重新解析 Session
+    Refresh,
+    /// 从远端 API 导入
+    RemoteImport,
+    /// 批量操作
+    BulkOp,
+    /// 生命周期自动评估（到期、积分耗尽等）
+    Lifecycle,
+    /// 从备份恢复
+    Restore,
+    /// 与远端双向同步
+    Sync,
+    /// 从本地文件导入
+    FileImport,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// 单个字段的变更，嵌套字段以点号连接，例如 portal_info.credits_balance
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub at: String,
+    pub source: ChangeSource,
+    pub action: ChangeAction,
+    pub changes: Vec<FieldChange>,
+}
+
+/// 获取历史文件路径
+/// 路径: <应用数据目录>/history.jsonl，每行一条记录，只追加不修改
+fn get_history_file_path() -> AppResult<PathBuf> {
+    Ok(crate::paths::data_dir()?.join("history.jsonl"))
+}
+
+/// 把记录展开为 字段路径 -> 值，敏感字段替换为遮蔽值
+fn flatten(token: &TokenRecord) -> BTreeMap<String, Value> {
+    let mut fields = BTreeMap::new();
+    if let Ok(Value::Object(map)) = serde_json::to_value(token) {
+        flatten_into(&mut fields, "", map, crate::vault::is_unlocked());
+    }
+    fields
+}
+
+fn flatten_into(fields: &mut BTreeMap<String, Value>, prefix: &str, map: Map<String, Value>, vault: bool) {
+    for (key, value) in map {
+        if prefix.is_empty() && IGNORED_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+
+        let path = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+        match value {
+            Value::Object(nested) => flatten_into(fields, &path, nested, vault),
+            Value::String(text) if SECRET_FIELDS.contains(&path.as_str()) && !text.is_empty() => {
+                fields.insert(path, Value::String(logging::mask(&text)));
+            }
+            Value::String(text) if vault && VAULT_MASKED_FIELDS.contains(&path.as_str()) && !text.is_empty() => {
+                fields.insert(path, Value::String(logging::mask_email(&text)));
+            }
+            value => {
+                fields.insert(path, value);
+            }
+        }
+    }
+}
+
+/// 比较两个版本的字段，缺失的一侧视为 null
+fn diff_fields(old: &BTreeMap<String, Value>, new: &BTreeMap<String, Value>) -> Vec<FieldChange> {
+    let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    paths.into_iter()
+        .filter_map(|path| {
+            let old_value = old.get(path).cloned().unwrap_or(Value::Null);
+            let new_value = new.get(path).cloned().unwrap_or(Value::Null);
+            (old_value != new_value).then(|| FieldChange {
+                field: path.clone(),
+                old: old_value,
+                new: new_value,
+            })
+        })
+        .collect()
+}
+
+/// 比较同一条记录的两个版本，缺失的一侧视为空记录
+pub fn record_changes(old: Option<&TokenRecord>, new: Option<&TokenRecord>) -> Vec<FieldChange> {
+    let old = old.map(flatten).unwrap_or_default();
+    let new = new.map(flatten).unwrap_or_default();
+    diff_fields(&old, &new)
+}
+
+/// 按 id 比较记录修改前后的版本，只出现在一侧的记录视为新增或删除
+/// 可以只传入发生变化的记录，不必传入完整列表
+pub fn diff<'a>(
+    old: impl IntoIterator<Item = &'a TokenRecord>,
+    new: impl IntoIterator<Item = &'a TokenRecord>,
+    source: ChangeSource,
+) -> Vec<HistoryEntry> {
+    let at = now_timestamp();
+    let empty = BTreeMap::new();
+    let old: Vec<&TokenRecord> = old.into_iter().collect();
+    let new: Vec<&TokenRecord> = new.into_iter().collect();
+    let old_by_id: HashMap<&str, &TokenRecord> = old.iter().map(|token| (token.id.as_str(), *token)).collect();
+    let new_by_id: HashMap<&str, &TokenRecord> = new.iter().map(|token| (token.id.as_str(), *token)).collect();
+
+    let entry = |id: &str, action, changes| HistoryEntry {
+        id: id.to_string(),
+        at: at.clone(),
+        source,
+        action,
+        changes,
+    };
+
+    let mut entries = Vec::new();
+    for token in new {
+        match old_by_id.get(token.id.as_str()) {
+            None => entries.push(entry(&token.id, ChangeAction::Created, diff_fields(&empty, &flatten(token)))),
+            Some(previous) if *previous != token => {
+                let changes = diff_fields(&flatten(previous), &flatten(token));
+                if !changes.is_empty() {
+                    entries.push(entry(&token.id, ChangeAction::Updated, changes));
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    for token in old {
+        if !new_by_id.contains_key(token.id.as_str()) {
+            entries.push(entry(&token.id, ChangeAction::Deleted, diff_fields(&flatten(token), &empty)));
+        }
+    }
+    entries
+}
+
+/// 追加历史记录
+pub fn append(entries: &[HistoryEntry]) -> AppResult<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut content = String::new();
+    for entry in entries {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| AppError::parse("序列化历史记录失败", e))?;
+        content.push_str(&line);
+        content.push('\n');
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_history_file_path()?)
+        .map_err(|e| AppError::io("打开历史文件失败", e))?;
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.sync_data())
+        .map_err(|e| AppError::io("写入历史文件失败", e))
+}
+
+/// 遮蔽已有历史中 VAULT_MASKED_FIELDS 的值（启用保险库时调用），无法解析的行原样保留
+pub fn mask_existing() -> AppResult<()> {
+    let file_path = get_history_file_path()?;
+    if !file_path.exists() {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(&file_path)
+        .map_err(|e| AppError::io("读取历史文件失败", e))?;
+
+    let mut masked = String::new();
+    for line in content.lines() {
+        let Ok(mut entry) = serde_json::from_str::<HistoryEntry>(line) else {
+            masked.push_str(line);
+            masked.push('\n');
+            continue;
+        };
+        for change in entry.changes.iter_mut().filter(|change| VAULT_MASKED_FIELDS.contains(&change.field.as_str())) {
+            for value in [&mut change.old, &mut change.new] {
+                if let Value::String(text) = value {
+                    *text = logging::mask_email(text);
+                }
+            }
+        }
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| AppError::parse("序列化历史记录失败", e))?;
+        masked.push_str(&line);
+        masked.push('\n');
+    }
+
+    crate::storage::atomic_write(&file_path, masked.as_bytes())
+}
+
+/// 读取某条记录的全部历史（从旧到新），跳过无法解析的行
+fn read(id: &str) -> AppResult<Vec<HistoryEntry>> {
+    let file_path = get_history_file_path()?;
+    if !file_path.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = fs::read_to_string(&file_path)
+        .map_err(|e| AppError::io("读取历史文件失败", e))?;
+
+    let mut entries = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<HistoryEntry>(line) {
+            Ok(entry) if entry.id == id => entries.push(entry),
+            Ok(_) => {}
+            Err(e) => log::warn!(line = index + 1; "跳过无法解析的历史记录: {}", e),
+        }
+    }
+    Ok(entries)
+}
+
+/// 获取记录的变更历史（从旧到新），已删除的记录同样可以查询
+#[tauri::command]
+pub async fn get_token_history(id: String) -> AppResult<Vec<HistoryEntry>> {
+    read(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_manager::PortalInfo;
+
+    fn token(id: &str) -> TokenRecord {
+        TokenRecord {
+            access_token: "a-very-long-access-token-value".to_string(),
+            ..TokenRecord::test(id, &format!("session-{}", id))
+        }
+    }
+
+    fn change<'a>(entry: &'a HistoryEntry, field: &str) -> Option<&'a FieldChange> {
+        entry.changes.iter().find(|change| change.field == field)
+    }
+
+    #[test]
+    fn diff_reports_created_updated_and_deleted() {
+        let old = vec![token("a"), token("b")];
+        let mut updated = token("a");
+        updated.updated_at = "2024-02-01T00:00:00Z".to_string();
+        updated.portal_info = Some(PortalInfo { credits_balance: Some(10), expiry_date: None });
+        let new = vec![updated, token("c")];
+
+        let entries = diff(&old, &new, ChangeSource::Manual);
+        let actions: Vec<(&str, ChangeAction)> = entries.iter().map(|entry| (entry.id.as_str(), entry.action)).collect();
+        assert_eq!(actions, vec![
+            ("a", ChangeAction::Updated),
+            ("c", ChangeAction::Created),
+            ("b", ChangeAction::Deleted),
+        ]);
+
+        // 嵌套字段以点号连接，updated_at 不计入变更
+        let updated = &entries[0];
+        assert_eq!(change(updated, "portal_info.credits_balance").unwrap().new, 10);
+        assert!(change(updated, "updated_at").is_none());
+    }
+
+    #[test]
+    fn only_ignored_fields_changed_is_not_an_update() {
+        let mut checked = token("a");
+        checked.updated_at = "2024-02-01T00:00:00Z".to_string();
+        assert!(diff(&[token("a")], &[checked], ChangeSource::Refresh).is_empty());
+    }
+
+    #[test]
+    fn secrets_are_masked() {
+        let entries = diff(&[], &[token("a")], ChangeSource::Manual);
+        let access_token = change(&entries[0], "access_token").unwrap();
+        assert_eq!(access_token.new, "a-ve…alue(len=30)");
+        assert_eq!(change(&entries[0], "auth_session").unwrap().new, "***(len=9)");
+        assert!(change(&entries[0], "id").is_none());
+    }
+
+    #[tokio::test]
+    async fn append_and_read_skip_bad_lines() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+
+        append(&diff(&[], &[token("a"), token("b")], ChangeSource::Manual)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(get_history_file_path().unwrap()).unwrap();
+        writeln!(file, "not json").unwrap();
+        append(&diff(&[token("a")], &[], ChangeSource::Manual)).unwrap();
+
+        let entries = read("a").unwrap();
+        let actions: Vec<ChangeAction> = entries.iter().map(|entry| entry.action).collect();
+        assert_eq!(actions, vec![ChangeAction::Created, ChangeAction::Deleted]);
+        assert!(read("missing").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn mask_existing_masks_email_notes_and_keeps_other_lines() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+
+        let noted = TokenRecord { email_note: Some("alice@example.com".to_string()), ..token("a") };
+        append(&diff(&[], &[noted], ChangeSource::Manual)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(get_history_file_path().unwrap()).unwrap();
+        writeln!(file, "not json").unwrap();
+
+        mask_existing().unwrap();
+
+        let entries = read("a").unwrap();
+        assert_eq!(change(&entries[0], "email_note").unwrap().new, "a***@example.com");
+        let content = fs::read_to_string(get_history_file_path().unwrap()).unwrap();
+        assert!(content.ends_with("not json\n"));
+    }
+}
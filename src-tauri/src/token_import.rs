@@ -0,0 +1,409 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::lifecycle::{self, BanStatus};
+use crate::token_history::{self, FieldChange};
+use crate::tombstone::{self, Tombstone};
+use crate::token_manager::{generate_id, now_timestamp, TokenRecord};
+use crate::token_manager::parse_timestamp;
+
+/// 导入时本地已存在相同 auth_session 的处理方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// 保留本地记录
+    #[default]
+    Skip,
+    /// 用导入的记录覆盖本地记录
+    Overwrite,
+    /// 按 updated_at 保留较新的一方
+    NewestWins,
+    /// 只填充本地为空的字段
+    FillEmpty,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeAction {
+    /// 新增记录
+    Import,
+    /// 更新了本地已有的记录
+    Update,
+    /// 未做任何修改
+    Skip,
+    /// 本地已删除该账号，不重新导入
+    Deleted,
+}
+
+/// 转换失败的远端记录
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportError {
+    /// 在远端返回数据中的位置
+    pub index: usize,
+    /// 缺失的必需字段
+    pub missing_fields: Vec<String>,
+    /// 值为空或格式无法识别的字段
+    pub invalid_fields: Vec<String>,
+    /// 字段以外的原因，例如记录不是对象
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// 脱敏后的原始记录片段
+    pub excerpt: String,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut reasons = Vec::new();
+        if !self.missing_fields.is_empty() {
+            reasons.push(format!("缺少必需字段: {}", self.missing_fields.join(", ")));
+        }
+        if !self.invalid_fields.is_empty() {
+            reasons.push(format!("字段值无效: {}", self.invalid_fields.join(", ")));
+        }
+        if let Some(reason) = &self.reason {
+            reasons.push(reason.clone());
+        }
+        write!(f, "{}", reasons.join("；"))
+    }
+}
+
+/// 预览中单条记录的处理方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewAction {
+    /// 将作为新记录导入
+    New,
+    /// 本地已存在，不做修改
+    Duplicate,
+    /// 将按合并策略更新本地记录
+    Update,
+    /// 本地已删除该账号，不会重新导入
+    Deleted,
+    /// 转换失败，不会导入
+    Invalid,
+}
+
+/// 预览中单条远端记录的结果
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PreviewRecord {
+    /// 在远端返回数据中的位置
+    pub index: usize,
+    /// 对应的本地记录 id；转换失败时为空
+    pub id: Option<String>,
+    pub action: PreviewAction,
+    /// 转换失败的原因
+    pub error: Option<ImportError>,
+    /// 新增和更新时为将要写入的变化；重复时为远端记录与本地记录的差异
+    pub changes: Vec<FieldChange>,
+}
+
+/// 在本地记录的副本上模拟合并，不修改任何数据
+/// 返回 (合并结果, 按输入顺序的逐条预览)
+pub fn preview(
+    local: &[TokenRecord],
+    tombstones: &[Tombstone],
+    incoming: Vec<Result<TokenRecord, ImportError>>,
+    strategy: MergeStrategy,
+) -> (Vec<MergeAction>, Vec<PreviewRecord>) {
+    let mut merged = local.to_vec();
+    let converted: Vec<TokenRecord> = incoming.iter()
+        .filter_map(|result| result.as_ref().ok().cloned())
+        .collect();
+    let actions = merge_into(&mut merged, tombstones, converted, strategy);
+
+    let before: HashMap<&str, &TokenRecord> = local.iter().map(|token| (token.auth_session.as_str(), token)).collect();
+    let after: HashMap<&str, &TokenRecord> = merged.iter().map(|token| (token.auth_session.as_str(), token)).collect();
+
+    let mut pending_actions = actions.iter();
+    let records = incoming.into_iter()
+        .enumerate()
+        .map(|(index, result)| {
+            let token = match result {
+                Ok(token) => token,
+                Err(error) => return PreviewRecord {
+                    index,
+                    id: None,
+                    action: PreviewAction::Invalid,
+                    error: Some(error),
+                    changes: vec![],
+                },
+            };
+
+            let session = token.auth_session.as_str();
+            let existing = before.get(session).copied();
+            let merged_token = after.get(session).copied();
+
+            let (action, changes) = match pending_actions.next() {
+                Some(MergeAction::Import) => (PreviewAction::New, token_history::record_changes(None, merged_token)),
+                Some(MergeAction::Update) => (PreviewAction::Update, token_history::record_changes(existing, merged_token)),
+                Some(MergeAction::Deleted) => (PreviewAction::Deleted, vec![]),
+                // 批次内重复的记录与先导入的那条比较
+                _ => (PreviewAction::Duplicate, token_history::record_changes(existing.or(merged_token), Some(&token))),
+            };
+
+            PreviewRecord {
+                index,
+                id: merged_token.map(|token| token.id.clone()),
+                action,
+                error: None,
+                changes,
+            }
+        })
+        .collect();
+
+    (actions, records)
+}
+
+/// 把导入的记录按策略合并到本地列表，按输入顺序返回每条记录的结果
+/// 同一批次中重复的 auth_session 只处理第一条；本地已删除的账号只有在删除后更新过才会重新导入
+pub fn merge_into(
+    local: &mut Vec<TokenRecord>,
+    tombstones: &[Tombstone],
+    incoming: Vec<TokenRecord>,
+    strategy: MergeStrategy,
+) -> Vec<MergeAction> {
+    let mut index_by_session: HashMap<String, usize> = local.iter()
+        .enumerate()
+        .map(|(index, token)| (token.auth_session.clone(), index))
+        .collect();
+    let mut used_ids: HashSet<String> = local.iter().map(|token| token.id.clone()).collect();
+    let mut seen_sessions = HashSet::new();
+
+    incoming.into_iter()
+        .map(|mut token| {
+            if !seen_sessions.insert(token.auth_session.clone()) {
+                return MergeAction::Skip;
+            }
+
+            let Some(&index) = index_by_session.get(&token.auth_session) else {
+                if tombstone::find(tombstones, &token).is_some_and(|tombstone| tombstone::blocks(tombstone, &token)) {
+                    return MergeAction::Deleted;
+                }
+                // id 与另一条本地记录冲突时重新生成，避免覆盖不相关的记录
+                if !used_ids.insert(token.id.clone()) {
+                    token.id = generate_id();
+                    used_ids.insert(token.id.clone());
+                }
+                index_by_session.insert(token.auth_session.clone(), local.len());
+                local.push(token);
+                return MergeAction::Import;
+            };
+
+            let existing = &mut local[index];
+            let changed = match strategy {
+                MergeStrategy::Skip => false,
+                MergeStrategy::Overwrite => overwrite(existing, token),
+                MergeStrategy::NewestWins => {
+                    if is_newer(&token, existing) {
+                        overwrite(existing, token)
+                    } else {
+                        false
+                    }
+                }
+                MergeStrategy::FillEmpty => fill_empty(existing, token),
+            };
+
+            if changed { MergeAction::Update } else { MergeAction::Skip }
+        })
+        .collect()
+}
+
+/// 导入记录的 updated_at 是否晚于本地；导入记录时间无法识别时视为较旧
+fn is_newer(incoming: &TokenRecord, local: &TokenRecord) -> bool {
+    match (parse_timestamp(&incoming.updated_at), parse_timestamp(&local.updated_at)) {
+        (Some(incoming), Some(local)) => incoming > local,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// 用导入的记录覆盖本地记录，保留本地的 id、创建时间和检查记录；返回是否发生变化
+fn overwrite(local: &mut TokenRecord, incoming: TokenRecord) -> bool {
+    let before = local.clone();
+    let status = incoming.ban_status;
+
+    *local = TokenRecord {
+        id: before.id.clone(),
+        created_at: before.created_at.clone(),
+        ban_status: before.ban_status,
+        last_check: before.last_check.clone(),
+        status_transitions: before.status_transitions.clone(),
+        ..incoming
+    };
+    lifecycle::set_status(local, status, "导入覆盖");
+
+    *local != before
+}
+
+/// 只填充本地为空的字段；返回是否发生变化
+fn fill_empty(local: &mut TokenRecord, incoming: TokenRecord) -> bool {
+    let mut changed = false;
+
+    fn fill_string(field: &mut String, value: String, changed: &mut bool) {
+        if field.is_empty() && !value.is_empty() {
+            *field = value;
+            *changed = true;
+        }
+    }
+
+    fn fill_option<T>(field: &mut Option<T>, value: Option<T>, changed: &mut bool) {
+        if field.is_none() && value.is_some() {
+            *field = value;
+            *changed = true;
+        }
+    }
+
+    fill_string(&mut local.tenant_url, incoming.tenant_url, &mut changed);
+    fill_string(&mut local.access_token, incoming.access_token, &mut changed);
+    fill_option(&mut local.portal_url, incoming.portal_url, &mut changed);
+    fill_option(&mut local.email_note, incoming.email_note, &mut changed);
+    fill_option(&mut local.tag_name, incoming.tag_name, &mut changed);
+    fill_option(&mut local.tag_color, incoming.tag_color, &mut changed);
+    fill_option(&mut local.suspensions, incoming.suspensions, &mut changed);
+    fill_option(&mut local.balance_color_mode, incoming.balance_color_mode, &mut changed);
+
+    match (&mut local.portal_info, incoming.portal_info) {
+        (None, Some(info)) => {
+            local.portal_info = Some(info);
+            changed = true;
+        }
+        (Some(local_info), Some(info)) => {
+            fill_option(&mut local_info.credits_balance, info.credits_balance, &mut changed);
+            fill_option(&mut local_info.expiry_date, info.expiry_date, &mut changed);
+        }
+        _ => {}
+    }
+
+    if local.ban_status == BanStatus::Unknown && incoming.ban_status != BanStatus::Unknown {
+        changed |= lifecycle::set_status(local, incoming.ban_status, "导入补全");
+    }
+
+    if changed {
+        local.updated_at = now_timestamp();
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_manager::RemoteTokenRecord;
+    use serde_json::json;
+
+    fn token(id: &str, session: &str, updated_at: &str, access_token: &str) -> TokenRecord {
+        TokenRecord {
+            updated_at: updated_at.to_string(),
+            access_token: access_token.to_string(),
+            ..TokenRecord::test(id, session)
+        }
+    }
+
+    #[test]
+    fn newest_wins_compares_updated_at() {
+        let mut local = vec![token("a", "s1", "2024-03-01T00:00:00Z", "local")];
+
+        let actions = merge_into(&mut local, &[], vec![token("x", "s1", "2024-02-01T00:00:00Z", "older")], MergeStrategy::NewestWins);
+        assert_eq!(actions, vec![MergeAction::Skip]);
+        assert_eq!(local[0].access_token, "local");
+
+        let actions = merge_into(&mut local, &[], vec![token("x", "s1", "2024-04-01T00:00:00Z", "newer")], MergeStrategy::NewestWins);
+        assert_eq!(actions, vec![MergeAction::Update]);
+        assert_eq!(local[0].access_token, "newer");
+        // 覆盖时保留本地 id
+        assert_eq!(local[0].id, "a");
+    }
+
+    #[test]
+    fn newest_wins_with_unparsable_timestamps() {
+        // 导入记录时间无法识别时视为较旧
+        let mut local = vec![token("a", "s1", "2024-03-01T00:00:00Z", "local")];
+        let actions = merge_into(&mut local, &[], vec![token("a", "s1", "yesterday", "incoming")], MergeStrategy::NewestWins);
+        assert_eq!(actions, vec![MergeAction::Skip]);
+        assert_eq!(local[0].access_token, "local");
+
+        // 本地时间无法识别时以导入记录为准
+        let mut local = vec![token("a", "s1", "not a date", "local")];
+        let actions = merge_into(&mut local, &[], vec![token("a", "s1", "2024-03-01T00:00:00Z", "incoming")], MergeStrategy::NewestWins);
+        assert_eq!(actions, vec![MergeAction::Update]);
+        assert_eq!(local[0].access_token, "incoming");
+
+        // 双方都无法识别时保留本地
+        let mut local = vec![token("a", "s1", "?", "local")];
+        let actions = merge_into(&mut local, &[], vec![token("a", "s1", "??", "incoming")], MergeStrategy::NewestWins);
+        assert_eq!(actions, vec![MergeAction::Skip]);
+    }
+
+    #[test]
+    fn duplicate_sessions_in_batch_and_conflicting_ids() {
+        let mut local = vec![token("a", "s1", "2024-01-01T00:00:00Z", "local")];
+        let actions = merge_into(
+            &mut local,
+            &[],
+            vec![
+                token("a", "s2", "2024-01-01T00:00:00Z", "first"),
+                token("b", "s2", "2024-01-01T00:00:00Z", "second"),
+            ],
+            MergeStrategy::Overwrite,
+        );
+        assert_eq!(actions, vec![MergeAction::Import, MergeAction::Skip]);
+        assert_eq!(local.len(), 2);
+        assert_eq!(local[1].access_token, "first");
+        // id 与本地另一条记录冲突时重新生成
+        assert_ne!(local[1].id, "a");
+    }
+
+    #[test]
+    fn fill_empty_only_fills_missing_fields() {
+        let mut local = vec![token("a", "s1", "2024-01-01T00:00:00Z", "")];
+        local[0].email_note = Some("local@example.com".to_string());
+
+        let mut incoming = token("b", "s1", "2024-01-01T00:00:00Z", "filled");
+        incoming.email_note = Some("remote@example.com".to_string());
+        incoming.tenant_url = "https://tenant.example.com".to_string();
+
+        let actions = merge_into(&mut local, &[], vec![incoming], MergeStrategy::FillEmpty);
+        assert_eq!(actions, vec![MergeAction::Update]);
+        assert_eq!(local[0].access_token, "filled");
+        assert_eq!(local[0].tenant_url, "https://tenant.example.com");
+        assert_eq!(local[0].email_note.as_deref(), Some("local@example.com"));
+    }
+
+    #[test]
+    fn tombstoned_accounts_are_not_reimported_unless_updated_later() {
+        let tombstones = vec![Tombstone {
+            id: "gone".to_string(),
+            session_hash: tombstone::session_hash("s1"),
+            deleted_at: "2024-02-01T00:00:00Z".to_string(),
+        }];
+
+        let mut local = vec![];
+        let actions = merge_into(&mut local, &tombstones, vec![token("other", "s1", "2024-01-15T00:00:00Z", "")], MergeStrategy::Skip);
+        assert_eq!(actions, vec![MergeAction::Deleted]);
+        assert!(local.is_empty());
+
+        let actions = merge_into(&mut local, &tombstones, vec![token("other", "s1", "2024-03-01T00:00:00Z", "")], MergeStrategy::Skip);
+        assert_eq!(actions, vec![MergeAction::Import]);
+    }
+
+    #[test]
+    fn preview_does_not_modify_local_records() {
+        let local = vec![token("a", "s1", "2024-01-01T00:00:00Z", "local")];
+        let invalid = RemoteTokenRecord::convert(&json!({ "id": "bad" }), 2, false);
+        let mut newer = token("a", "s1", "2024-05-01T00:00:00Z", "newer");
+        newer.email_note = Some("new note".to_string());
+        let incoming = vec![
+            Ok(newer),
+            Ok(token("b", "s2", "2024-01-01T00:00:00Z", "new")),
+            invalid,
+        ];
+
+        let (actions, records) = preview(&local, &[], incoming, MergeStrategy::NewestWins);
+        assert_eq!(actions, vec![MergeAction::Update, MergeAction::Import]);
+        let kinds: Vec<PreviewAction> = records.iter().map(|record| record.action).collect();
+        assert_eq!(kinds, vec![PreviewAction::Update, PreviewAction::New, PreviewAction::Invalid]);
+        assert!(records[0].changes.iter().any(|change| change.field == "email_note"));
+        assert_eq!(records[2].error.as_ref().unwrap().missing_fields, vec!["auth_session", "created_at"]);
+        assert_eq!(local[0].access_token, "local");
+    }
+}
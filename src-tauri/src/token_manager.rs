@@ -1,14 +1,28 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use tauri::State;
+
+use crate::config::StorageBackend;
+use crate::error::{AppError, AppResult};
+use crate::lifecycle::{BanStatus, CheckResult, StatusTransition};
+use crate::logging;
+use crate::remote_mapping::RemoteMapping;
+use crate::remote_source::{self, RemoteRequest};
+use crate::storage::{self, BackupInfo};
+use crate::token_db;
+use crate::token_history::ChangeSource;
+use crate::token_import::{self, ImportError, MergeAction, MergeStrategy, PreviewRecord};
+use crate::token_schema;
+use crate::token_store::{self, TokenStore};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PortalInfo {
     pub credits_balance: Option<i32>,
     pub expiry_date: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct TokenRecord {
     pub id: String,
     pub tenant_url: String,
@@ -16,7 +30,7 @@ pub struct TokenRecord {
     pub created_at: String,
     pub updated_at: String,
     pub portal_url: Option<String>,
-    pub ban_status: String,
+    pub ban_status: BanStatus,
     pub portal_info: Option<PortalInfo>,
     pub email_note: Option<String>,
     pub tag_name: Option<String>,
@@ -25,9 +39,57 @@ pub struct TokenRecord {
     pub suspensions: Option<String>,
     pub skip_check: bool,
     pub balance_color_mode: Option<String>,
+    /// 最近一次 Session 检查的结果
+    #[serde(default)]
+    pub last_check: Option<CheckResult>,
+    /// 账号状态变更记录（从旧到新）
+    #[serde(default)]
+    pub status_transitions: Vec<StatusTransition>,
+}
+
+/// 只在本地使用、不导出到远端或文件的字段
+const LOCAL_ONLY_FIELDS: &[&str] = &["last_check", "status_transitions"];
+
+impl TokenRecord {
+    /// 转换为 RemoteTokenRecord 可以接受的结构，去掉只在本地使用的字段
+    pub fn to_remote_value(&self) -> AppResult<serde_json::Value> {
+        let mut value = serde_json::to_value(self)
+            .map_err(|e| AppError::parse("序列化记录失败", e))?;
+        if let serde_json::Value::Object(map) = &mut value {
+            for field in LOCAL_ONLY_FIELDS {
+                map.remove(*field);
+            }
+        }
+        Ok(value)
+    }
+
+    /// 测试用的最小记录，其他字段在测试中按需修改
+    #[cfg(test)]
+    pub fn test(id: &str, auth_session: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            tenant_url: String::new(),
+            access_token: String::new(),
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+            portal_url: None,
+            ban_status: BanStatus::Active,
+            portal_info: None,
+            email_note: None,
+            tag_name: None,
+            tag_color: None,
+            auth_session: auth_session.to_string(),
+            suspensions: None,
+            skip_check: false,
+            balance_color_mode: None,
+            last_check: None,
+            status_transitions: vec![],
+        }
+    }
 }
 
 // 远端 API 返回的 Token 数据结构（字段可选）
+// 字段名不同的远端可以通过 RemoteMapping 映射到这里的标准字段
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RemoteTokenRecord {
     #[serde(default)]
@@ -60,147 +122,142 @@ pub struct RemoteTokenRecord {
     pub skip_check: Option<bool>,
     #[serde(default)]
     pub balance_color_mode: Option<String>,
-
-    // 兼容其他可能的字段名
-    #[serde(default, alias = "tenantUrl")]
-    pub tenant_url_alt: Option<String>,
-    #[serde(default, alias = "accessToken")]
-    pub access_token_alt: Option<String>,
-    #[serde(default, alias = "authSession")]
-    pub auth_session_alt: Option<String>,
-    #[serde(default, alias = "emailNote")]
-    pub email_note_alt: Option<String>,
-    #[serde(default, alias = "banStatus")]
-    pub ban_status_alt: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct RemoteApiResponse {
-    pub status: i32,
-    pub data: Vec<RemoteTokenRecord>,
+pub struct StoreInfo {
+    pub backend: StorageBackend,
+    /// 磁盘上存储结构的版本（旧版本会在下次写入时升级）
+    pub schema_version: u32,
+    /// 当前应用使用的存储结构版本
+    pub latest_schema_version: u32,
+    pub record_count: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ImportResult {
     pub imported: usize,
+    /// 按合并策略更新的本地记录数
+    pub updated: usize,
     pub skipped: usize,
+    /// 转换失败的远端记录数
+    pub failed: usize,
+    /// 转换失败的远端记录及原因
+    pub errors: Vec<ImportError>,
+    /// 预览模式下每条远端记录的处理结果；实际导入时为空
+    pub preview: Option<Vec<PreviewRecord>>,
+}
+
+impl ImportResult {
+    pub fn from_actions(actions: &[MergeAction], errors: Vec<ImportError>) -> Self {
+        let count = |action| actions.iter().filter(|&&a| a == action).count();
+        Self {
+            imported: count(MergeAction::Import),
+            updated: count(MergeAction::Update),
+            skipped: count(MergeAction::Skip) + count(MergeAction::Deleted),
+            failed: errors.len(),
+            errors,
+            preview: None,
+        }
+    }
 }
 
 impl RemoteTokenRecord {
+    /// 把一条原始记录转换为本地 TokenRecord
+    /// 记录不是对象或字段类型不符时同样返回 ImportError，不影响其他记录
+    pub(crate) fn convert(value: &serde_json::Value, index: usize, generate_missing: bool) -> Result<TokenRecord, ImportError> {
+        match Self::deserialize(value) {
+            Ok(record) => record.to_local_token(index, generate_missing),
+            Err(e) => Err(ImportError {
+                index,
+                missing_fields: vec![],
+                invalid_fields: vec![],
+                reason: Some(format!("记录格式无效: {}", e)),
+                excerpt: logging::truncate(&logging::redact(&value.to_string()), 300),
+            }),
+        }
+    }
+
     /// 转换为本地 TokenRecord 格式，并填充缺失字段的默认值
-    /// 必需字段：id, auth_session, created_at
+    /// 必需字段：id, auth_session, created_at；generate_missing 为 true 时自动生成缺失的 id 和 created_at
     /// 其他字段：填充默认值或 null
-    fn to_local_token(&self) -> Result<TokenRecord, String> {
-        println!("    📝 开始转换远端数据为本地格式...");
-
+    pub(crate) fn to_local_token(&self, index: usize, generate_missing: bool) -> Result<TokenRecord, ImportError> {
         // ========== 第一步：提取远端 API 返回的必需字段 ==========
 
+        let mut missing_fields = Vec::new();
+        let mut invalid_fields = Vec::new();
+
+        // 空白字符串视为无效值
+        let mut required = |field: &str, value: Option<String>| match value {
+            None => {
+                missing_fields.push(field.to_string());
+                None
+            }
+            Some(value) if value.trim().is_empty() => {
+                invalid_fields.push(field.to_string());
+                None
+            }
+            Some(value) => Some(value),
+        };
+
         // 必需字段 1: id
-        let id = self.id.clone()
-            .ok_or("缺少必需字段: id")?;
-        println!("      ✓ id: {}", id);
+        let id = required("id", self.id.clone());
 
         // 必需字段 2: auth_session
-        let auth_session = self.auth_session.clone()
-            .or_else(|| self.auth_session_alt.clone())
-            .ok_or("缺少必需字段: auth_session")?;
-        println!("      ✓ auth_session: {}", if auth_session.len() > 20 { &auth_session[..20] } else { &auth_session });
+        let auth_session = required("auth_session", self.auth_session.clone());
 
         // 必需字段 3: created_at
-        let created_at = self.created_at.clone()
-            .ok_or("缺少必需字段: created_at")?;
-        println!("      ✓ created_at: {}", created_at);
-
-        // ========== 第二步：提取可选字段（如果远端提供） ==========
-
-        // 提取可选字段
-        let tenant_url = self.tenant_url.clone()
-            .or_else(|| self.tenant_url_alt.clone());
-        if let Some(ref url) = tenant_url {
-            println!("      ✓ tenant_url: {}", url);
+        let created_at = required("created_at", self.created_at.clone());
+        let mut reason = None;
+        if let Some(value) = &created_at {
+            if parse_timestamp(value).is_none() {
+                invalid_fields.push("created_at".to_string());
+                reason = Some(format!("无法识别的时间格式: {}", logging::truncate(value, 40)));
+            }
         }
 
-        let access_token = self.access_token.clone()
-            .or_else(|| self.access_token_alt.clone());
-        if let Some(ref token) = access_token {
-            println!("      ✓ access_token: {}", if token.len() > 20 { &token[..20] } else { token });
+        if generate_missing {
+            missing_fields.retain(|field| field != "id" && field != "created_at");
         }
-
-        let email_note = self.email_note.clone()
-            .or_else(|| self.email_note_alt.clone());
-        if let Some(ref email) = email_note {
-            println!("      ✓ email_note: {}", email);
+        if !missing_fields.is_empty() || !invalid_fields.is_empty() {
+            return Err(ImportError {
+                index,
+                missing_fields,
+                invalid_fields,
+                reason,
+                excerpt: self.excerpt(),
+            });
         }
 
-        let portal_info = self.portal_info.clone();
-        if let Some(ref info) = portal_info {
-            println!("      ✓ portal_info: credits={:?}, expiry={:?}",
-                info.credits_balance, info.expiry_date);
-        }
+        let id = id.unwrap_or_else(generate_id);
+        let auth_session = auth_session.unwrap_or_default();
+        let created_at = created_at.unwrap_or_else(now_timestamp);
 
-        let ban_status = self.ban_status.clone()
-            .or_else(|| self.ban_status_alt.clone());
-        if let Some(ref status) = ban_status {
-            println!("      ✓ ban_status: {}", status);
-        }
+        // ========== 第二步：提取远端可能返回的可选字段 ==========
 
-        // ========== 第三步：填充缺失字段的默认值 ==========
-        println!("    🔧 填充缺失字段的默认值或 null...");
+        let tenant_url = self.tenant_url.clone();
+        let access_token = self.access_token.clone();
+        let email_note = self.email_note.clone();
+        let portal_info = self.portal_info.clone();
+        let ban_status = self.ban_status.clone();
 
-        // tenant_url: 默认空字符串
-        let tenant_url = tenant_url.unwrap_or_else(|| {
-            println!("      → tenant_url: \"\" (默认空字符串)");
-            String::new()
-        });
+        // ========== 第三步：填充缺失字段的默认值 ==========
 
-        // access_token: 默认空字符串
-        let access_token = access_token.unwrap_or_else(|| {
-            println!("      → access_token: \"\" (默认空字符串)");
-            String::new()
-        });
+        // tenant_url / access_token: 默认空字符串
+        let tenant_url = tenant_url.unwrap_or_default();
+        let access_token = access_token.unwrap_or_default();
 
         // updated_at: 使用 created_at 的值
-        let updated_at = self.updated_at.clone().unwrap_or_else(|| {
-            println!("      → updated_at: {} (使用 created_at 的值)", created_at);
-            created_at.clone()
-        });
+        let updated_at = self.updated_at.clone().unwrap_or_else(|| created_at.clone());
 
-        // ban_status: 默认 "ACTIVE"
-        let ban_status = ban_status.unwrap_or_else(|| {
-            println!("      → ban_status: \"ACTIVE\" (默认值)");
-            "ACTIVE".to_string()
-        });
+        // ban_status: 默认 ACTIVE，兼容非标准写法
+        let ban_status = ban_status.as_deref().map(BanStatus::parse).unwrap_or(BanStatus::Active);
 
         // skip_check: 默认 false
-        let skip_check = self.skip_check.unwrap_or_else(|| {
-            println!("      → skip_check: false (默认值)");
-            false
-        });
+        let skip_check = self.skip_check.unwrap_or(false);
 
         // 其他字段保持 null
-        if self.portal_url.is_none() {
-            println!("      → portal_url: null");
-        }
-        if email_note.is_none() {
-            println!("      → email_note: null");
-        }
-        if portal_info.is_none() {
-            println!("      → portal_info: null");
-        }
-        if self.tag_name.is_none() {
-            println!("      → tag_name: null");
-        }
-        if self.tag_color.is_none() {
-            println!("      → tag_color: null");
-        }
-        if self.suspensions.is_none() {
-            println!("      → suspensions: null");
-        }
-        if self.balance_color_mode.is_none() {
-            println!("      → balance_color_mode: null");
-        }
-
-        println!("    ✅ 转换完成");
+        log::debug!(id = id.as_str(), auth_session = auth_session.as_str(); "远端记录转换完成");
 
         Ok(TokenRecord {
             id,
@@ -218,226 +275,330 @@ impl RemoteTokenRecord {
             suspensions: self.suspensions.clone(),
             skip_check,
             balance_color_mode: self.balance_color_mode.clone(),
+            last_check: None,
+            status_transitions: vec![],
         })
     }
+
+    /// 生成用于排查的记录片段：省略空字段，遮蔽 session 和 token，并做通用脱敏
+    fn excerpt(&self) -> String {
+        let Ok(serde_json::Value::Object(map)) = serde_json::to_value(self) else {
+            return String::new();
+        };
+
+        let fields: serde_json::Map<String, serde_json::Value> = map.into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| match value {
+                serde_json::Value::String(text) if key.contains("session") || key.contains("token") => {
+                    (key, serde_json::Value::String(logging::mask(&text)))
+                }
+                value => (key, value),
+            })
+            .collect();
+
+        let text = serde_json::Value::Object(fields).to_string();
+        logging::truncate(&logging::redact(&text), 300)
+    }
 }
 
 /// 获取 tokens.json 文件路径
-/// 路径: %APPDATA%\com.lantianzhi.aug-session-sync\tokens.json
-fn get_tokens_file_path() -> Result<PathBuf, String> {
-    use std::env;
-
-    // 获取 APPDATA 环境变量
-    let app_data = env::var("APPDATA")
-        .or_else(|_| env::var("HOME").map(|home| format!("{}/.config", home)))
-        .map_err(|e| format!("获取应用数据目录失败: {}", e))?;
-
-    // 构建应用数据目录路径
-    let app_dir = PathBuf::from(app_data).join("com.lantianzhi.aug-session-sync");
-
-    // 确保目录存在
-    if !app_dir.exists() {
-        fs::create_dir_all(&app_dir)
-            .map_err(|e| format!("创建应用数据目录失败: {}", e))?;
+/// 路径: <应用数据目录>/tokens.json
+pub(crate) fn get_tokens_file_path() -> AppResult<PathBuf> {
+    Ok(crate::paths::data_dir()?.join("tokens.json"))
+}
+
+/// 生成新的记录 id（UUID v4 格式）
+pub(crate) fn generate_id() -> String {
+    let bytes: [u8; 16] = rand::random();
+    let mut value = u128::from_be_bytes(bytes);
+    // 设置版本号 4 与 RFC 4122 变体位
+    value = (value & !(0xf << 76)) | (0x4 << 76);
+    value = (value & !(0x3 << 62)) | (0x2 << 62);
+
+    let hex = format!("{:032x}", value);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// 当前时间，格式与前端 `new Date().toISOString()` 一致
+pub(crate) fn now_timestamp() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// 解析记录中的时间字符串，无法识别时返回 None
+pub(crate) fn parse_timestamp(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let value = value.trim();
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&chrono::Utc));
     }
+    if let Ok(time) = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Some(time.and_utc());
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+}
 
-    Ok(app_dir.join("tokens.json"))
+/// 读取全部 token 记录
+#[tauri::command]
+pub async fn read_tokens(store: State<'_, TokenStore>) -> AppResult<Vec<TokenRecord>> {
+    store.list().await
 }
 
-/// 读取 tokens.json 文件
+/// 覆盖写入全部 token 记录
 #[tauri::command]
-pub async fn read_tokens() -> Result<Vec<TokenRecord>, String> {
-    let file_path = get_tokens_file_path()?;
-    
-    // 如果文件不存在，创建空数组文件
-    if !file_path.exists() {
-        fs::write(&file_path, "[]")
-            .map_err(|e| format!("创建 tokens.json 失败: {}", e))?;
-        return Ok(vec![]);
-    }
-    
-    let content = fs::read_to_string(&file_path)
-        .map_err(|e| format!("读取 tokens.json 失败: {}", e))?;
-    
-    let tokens: Vec<TokenRecord> = serde_json::from_str(&content)
-        .map_err(|e| format!("解析 tokens.json 失败: {}", e))?;
-    
-    Ok(tokens)
+pub async fn write_tokens(store: State<'_, TokenStore>, tokens: Vec<TokenRecord>) -> AppResult<()> {
+    store.mutate(ChangeSource::Manual, |current| {
+        *current = tokens;
+        Ok(())
+    }).await
 }
 
-/// 写入 tokens.json 文件
+/// 查询存储信息：后端类型、结构版本和记录数
 #[tauri::command]
-pub async fn write_tokens(tokens: Vec<TokenRecord>) -> Result<(), String> {
-    let file_path = get_tokens_file_path()?;
-    
-    let json_string = serde_json::to_string_pretty(&tokens)
-        .map_err(|e| format!("序列化 tokens 失败: {}", e))?;
-    
-    fs::write(&file_path, json_string)
-        .map_err(|e| format!("写入 tokens.json 失败: {}", e))?;
-    
-    Ok(())
+pub async fn store_info(store: State<'_, TokenStore>) -> AppResult<StoreInfo> {
+    let (backend, schema_version, record_count) = store.info().await?;
+
+    let latest_schema_version = match backend {
+        StorageBackend::Json => token_schema::CURRENT_SCHEMA_VERSION,
+        StorageBackend::Sqlite => token_db::DB_SCHEMA_VERSION,
+    };
+
+    Ok(StoreInfo { backend, schema_version, latest_schema_version, record_count })
 }
 
-/// 添加单个 token 记录
+/// 列出 tokens.json 的历史备份（从新到旧）
 #[tauri::command]
-pub async fn add_token(token: TokenRecord) -> Result<(), String> {
-    let mut tokens = read_tokens().await?;
-    
-    // 检查是否已存在相同的 auth_session
-    if tokens.iter().any(|t| t.auth_session == token.auth_session) {
-        return Err("该 Session 已存在".to_string());
-    }
-    
-    tokens.push(token);
-    write_tokens(tokens).await?;
-    
-    Ok(())
+pub async fn list_tokens_backups() -> AppResult<Vec<BackupInfo>> {
+    let file_path = get_tokens_file_path()?;
+    storage::list_backups(&file_path)
 }
 
-/// 从远端 API 导入 tokens
+/// 从指定备份恢复 tokens.json
+/// 恢复前当前文件同样会被备份，因此恢复操作本身也可以撤销
 #[tauri::command]
-pub async fn import_from_remote(api_url: String) -> Result<ImportResult, String> {
-    println!("=== 后端：开始从远端 API 导入 ===");
-    println!("API 地址: {}", api_url);
-
-    // 调用远端 API
-    println!("步骤1: 创建 HTTP 客户端...");
-    let client = crate::http_client::create_client()?;
-
-    println!("步骤2: 发送 GET 请求...");
-    let response = client
-        .get(&api_url)
-        .send()
-        .await
-        .map_err(|e| format!("请求远端 API 失败: {}", e))?;
-
-    println!("步骤3: 接收响应");
-    println!("  - HTTP 状态码: {}", response.status());
-    println!("  - 响应头:");
-    for (key, value) in response.headers() {
-        println!("    {}: {:?}", key, value);
-    }
+pub async fn restore_tokens_backup(store: State<'_, TokenStore>, file_name: String) -> AppResult<()> {
+    let file_path = get_tokens_file_path()?;
+    let backup_path = storage::backup_path(&file_path, &file_name)?;
 
-    if !response.status().is_success() {
-        println!("=== 后端：导入失败（HTTP 错误）===");
-        return Err(format!("远端 API 返回错误: {}", response.status()));
-    }
+    let content = fs::read_to_string(&backup_path)
+        .map_err(|e| AppError::io("读取备份失败", e))?;
 
-    println!("步骤4: 解析 JSON 响应体...");
-    let response_text = response.text().await
-        .map_err(|e| format!("读取响应体失败: {}", e))?;
-    println!("  - 响应体长度: {} 字节", response_text.len());
-    println!("  - 响应体内容（前 500 字符）: {}",
-        if response_text.len() > 500 { &response_text[..500] } else { &response_text });
-
-    let api_response: RemoteApiResponse = serde_json::from_str(&response_text)
-        .map_err(|e| {
-            println!("  ❌ JSON 解析失败: {}", e);
-            println!("  完整响应体: {}", response_text);
-            format!("解析远端 API 响应失败: {}", e)
-        })?;
-
-    println!("步骤5: 验证响应数据");
-    println!("  - status 字段: {}", api_response.status);
-    println!("  - data 数组长度: {}", api_response.data.len());
-
-    // 打印第一条记录的结构（用于调试）
-    if !api_response.data.is_empty() {
-        println!("  - 第一条记录示例:");
-        println!("    {:?}", api_response.data[0]);
-    }
+    let (_, data) = token_store::decode_tokens(&content)
+        .map_err(|e| AppError::new(e.code, "备份文件无法恢复").with_details(e))?;
 
-    // 检查 status 字段
-    if api_response.status != 1 {
-        println!("=== 后端：导入失败（status != 1）===");
-        return Err("远端 API 返回失败状态".to_string());
-    }
+    // 只恢复记录，当前回收站保留；备份中不存在的记录会移入回收站
+    let tokens = data.tokens;
 
-    println!("远端 API 返回 {} 条记录", api_response.data.len());
-    
-    // 读取本地 tokens
-    println!("步骤6: 读取本地 tokens...");
-    let mut local_tokens = read_tokens().await?;
-    println!("  - 本地现有记录数: {}", local_tokens.len());
-
-    // 转换并合并数据（填充默认值 + 去重）
-    println!("步骤7: 转换远端数据并填充默认值...");
-    println!("  说明: 远端 API 只返回核心字段，本地会自动填充缺失字段的默认值");
-    println!("");
-
-    let mut imported = 0;
-    let mut skipped = 0;
-    let mut conversion_errors = 0;
-
-    for (index, remote_token) in api_response.data.iter().enumerate() {
-        println!("  📦 处理第 {} 条记录", index + 1);
-
-        // 转换为本地格式（提取远端字段 + 填充默认值）
-        let local_token = match remote_token.to_local_token() {
-            Ok(token) => {
-                println!("    ✅ 转换成功");
-                token
-            },
-            Err(e) => {
-                conversion_errors += 1;
-                println!("    ❌ 转换失败: {}", e);
-                println!("    原始数据: {:?}", remote_token);
-                println!("");
-                continue;
-            }
-        };
+    store.mutate(ChangeSource::Restore, |current| {
+        *current = tokens;
+        Ok(())
+    }).await
+}
 
-        // 检查是否重复（基于 auth_session）
-        if local_tokens.iter().any(|t| t.auth_session == local_token.auth_session) {
-            skipped += 1;
-            println!("    ⏭️  跳过重复记录 (auth_session 已存在)");
-            println!("    邮箱: {}", local_token.email_note.as_deref().unwrap_or("未知"));
-        } else {
-            println!("    ✅ 添加到本地数据库");
-            println!("    邮箱: {}", local_token.email_note.as_deref().unwrap_or("未知"));
-            local_tokens.push(local_token);
-            imported += 1;
-        }
-        println!("");
+/// 添加单个 token 记录
+#[tauri::command]
+pub async fn add_token(store: State<'_, TokenStore>, token: TokenRecord) -> AppResult<()> {
+    // 检查是否已存在相同的 auth_session
+    if store.find_by_session(&token.auth_session).await?.is_some() {
+        return Err(AppError::duplicate("该 Session 已存在"));
     }
 
-    if conversion_errors > 0 {
-        println!("  ⚠️  转换错误统计: {} 条记录无法转换（缺少必需字段）", conversion_errors);
-        println!("");
-    }
+    store.mutate(ChangeSource::Manual, |tokens| {
+        // 查询与写入之间可能有并发的添加，锁内再确认一次
+        if tokens.iter().any(|t| t.auth_session == token.auth_session) {
+            return Err(AppError::duplicate("该 Session 已存在"));
+        }
 
-    // 写入本地文件
-    println!("步骤8: 写入本地文件...");
-    write_tokens(local_tokens).await?;
+        tokens.push(token);
+        Ok(())
+    }).await
+}
 
-    println!("=== 后端：导入完成 ===");
-    println!("  - 成功导入: {} 条", imported);
-    println!("  - 跳过重复: {} 条", skipped);
+/// 从远端 API 导入 tokens
+/// strategy 决定本地已存在相同 auth_session 时的处理方式，默认跳过
+/// dry_run 为 true 时只预览每条记录的处理结果，不写入任何数据
+/// generate_missing 为 true 时为缺少 id / created_at 的记录自动生成，而不是拒绝导入
+/// request 配置请求方式、请求头和分页，默认不带认证的单次 GET；所有分页汇总后再合并
+/// mapping 描述响应结构和字段来源，默认为 { "status": 1, "data": [...] } 或直接返回数组
+#[tauri::command]
+pub async fn import_from_remote(
+    store: State<'_, TokenStore>,
+    api_url: String,
+    request: Option<RemoteRequest>,
+    mapping: Option<RemoteMapping>,
+    strategy: Option<MergeStrategy>,
+    dry_run: Option<bool>,
+    generate_missing: Option<bool>,
+) -> AppResult<ImportResult> {
+    run_remote_import(
+        &store,
+        &api_url,
+        &request.unwrap_or_default(),
+        &mapping.unwrap_or_default(),
+        strategy.unwrap_or_default(),
+        dry_run.unwrap_or(false),
+        generate_missing.unwrap_or(false),
+    ).await
+}
+
+/// 从远端拉取记录并合并到本地，供导入命令和定时导入共用
+pub(crate) async fn run_remote_import(
+    store: &TokenStore,
+    api_url: &str,
+    request: &RemoteRequest,
+    mapping: &RemoteMapping,
+    strategy: MergeStrategy,
+    dry_run: bool,
+    generate_missing: bool,
+) -> AppResult<ImportResult> {
+    log::info!(url = api_url, dry_run = dry_run; "开始从远端 API 导入");
+
+    let remote_tokens = remote_source::fetch_records(api_url, request, mapping).await?;
+
+    // 转换远端数据（填充默认值），单条记录格式错误只计入该记录的错误
+    let converted: Vec<Result<TokenRecord, ImportError>> = remote_tokens.iter()
+        .enumerate()
+        .map(|(index, value)| {
+            RemoteTokenRecord::convert(value, index, generate_missing).inspect_err(|e| {
+                log::warn!(index = index, excerpt = e.excerpt.as_str(); "远端记录转换失败: {}", e);
+            })
+        })
+        .collect();
+    let errors: Vec<ImportError> = converted.iter()
+        .filter_map(|result| result.as_ref().err().cloned())
+        .collect();
+
+    if dry_run {
+        let data = store.data().await?;
+        let (actions, records) = token_import::preview(&data.tokens, &data.tombstones, converted, strategy);
+        return Ok(ImportResult {
+            preview: Some(records),
+            ..ImportResult::from_actions(&actions, errors)
+        });
+    }
 
-    Ok(ImportResult { imported, skipped })
+    // 合并到本地存储（基于 auth_session 匹配）
+    let converted = converted.into_iter().filter_map(Result::ok).collect();
+    let result = store.mutate_data(ChangeSource::RemoteImport, |data| {
+        let actions = token_import::merge_into(&mut data.tokens, &data.tombstones, converted, strategy);
+        Ok(ImportResult::from_actions(&actions, errors))
+    }).await?;
+
+    log::info!(
+        total = remote_tokens.len(),
+        imported = result.imported,
+        updated = result.updated,
+        skipped = result.skipped,
+        failed = result.failed;
+        "远端导入完成"
+    );
+
+    Ok(result)
 }
 
-/// 删除 token 记录
+/// 删除 token 记录（移入回收站）
 #[tauri::command]
-pub async fn delete_token(id: String) -> Result<(), String> {
-    let mut tokens = read_tokens().await?;
-    tokens.retain(|t| t.id != id);
-    write_tokens(tokens).await?;
-    Ok(())
+pub async fn delete_token(store: State<'_, TokenStore>, id: String) -> AppResult<()> {
+    store.mutate(ChangeSource::Manual, |tokens| {
+        tokens.retain(|t| t.id != id);
+        Ok(())
+    }).await
 }
 
 /// 更新 token 记录
 #[tauri::command]
-pub async fn update_token(token: TokenRecord) -> Result<(), String> {
-    let mut tokens = read_tokens().await?;
-    
-    if let Some(index) = tokens.iter().position(|t| t.id == token.id) {
-        tokens[index] = token;
-        write_tokens(tokens).await?;
-        Ok(())
-    } else {
-        Err("未找到指定的 Token 记录".to_string())
+pub async fn update_token(store: State<'_, TokenStore>, token: TokenRecord) -> AppResult<()> {
+    store.mutate(ChangeSource::Manual, |tokens| {
+        match tokens.iter_mut().find(|t| t.id == token.id) {
+            Some(existing) => {
+                *existing = token;
+                Ok(())
+            }
+            None => Err(AppError::not_found("未找到指定的 Token 记录")),
+        }
+    }).await
+}
+
+/// 界面可以编辑的字段，未提供的字段保持不变
+/// 可空字段传 null 或空字符串表示清空，例如 `{ "email_note": null }`
+/// 检查结果、状态变更等由后台任务维护的字段不在其中，编辑时不会覆盖后台的更新
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct TokenUpdate {
+    pub tenant_url: Option<String>,
+    pub access_token: Option<String>,
+    pub auth_session: Option<String>,
+    pub skip_check: Option<bool>,
+    #[serde(deserialize_with = "crate::token_bulk::present")]
+    pub portal_url: Option<Option<String>>,
+    #[serde(deserialize_with = "crate::token_bulk::present")]
+    pub email_note: Option<Option<String>>,
+    #[serde(deserialize_with = "crate::token_bulk::present")]
+    pub tag_name: Option<Option<String>>,
+    #[serde(deserialize_with = "crate::token_bulk::present")]
+    pub tag_color: Option<Option<String>>,
+    #[serde(deserialize_with = "crate::token_bulk::present")]
+    pub suspensions: Option<Option<String>>,
+    #[serde(deserialize_with = "crate::token_bulk::present")]
+    pub balance_color_mode: Option<Option<String>>,
+}
+
+impl TokenUpdate {
+    fn apply(self, token: &mut TokenRecord) {
+        if let Some(tenant_url) = self.tenant_url {
+            token.tenant_url = tenant_url;
+        }
+        if let Some(access_token) = self.access_token {
+            token.access_token = access_token;
+        }
+        if let Some(auth_session) = self.auth_session {
+            token.auth_session = auth_session;
+        }
+        if let Some(skip_check) = self.skip_check {
+            token.skip_check = skip_check;
+        }
+        for (field, value) in [
+            (&mut token.portal_url, self.portal_url),
+            (&mut token.email_note, self.email_note),
+            (&mut token.tag_name, self.tag_name),
+            (&mut token.tag_color, self.tag_color),
+            (&mut token.suspensions, self.suspensions),
+            (&mut token.balance_color_mode, self.balance_color_mode),
+        ] {
+            if let Some(value) = value {
+                // 表单清空输入框时提交的是空字符串，与 null 一样视为清空
+                *field = value.filter(|value| !value.trim().is_empty());
+            }
+        }
     }
 }
 
+/// 只更新 token 记录中界面可编辑的字段，返回更新后的记录
+/// expected_updated_at 为编辑开始时记录的 updated_at，记录已被其他操作（例如后台检查）更新时拒绝保存
+#[tauri::command]
+pub async fn update_token_fields(
+    store: State<'_, TokenStore>,
+    id: String,
+    changes: TokenUpdate,
+    expected_updated_at: Option<String>,
+) -> AppResult<TokenRecord> {
+    store.mutate(ChangeSource::Manual, |tokens| {
+        if let Some(session) = &changes.auth_session {
+            if tokens.iter().any(|t| t.id != id && &t.auth_session == session) {
+                return Err(AppError::duplicate("该 Session 已存在"));
+            }
+        }
+
+        let existing = tokens.iter_mut()
+            .find(|t| t.id == id)
+            .ok_or_else(|| AppError::not_found("未找到指定的 Token 记录"))?;
+        if expected_updated_at.as_ref().is_some_and(|expected| expected != &existing.updated_at) {
+            return Err(AppError::invalid_state("记录已被其他操作更新，请刷新后重新编辑"));
+        }
+
+        changes.apply(existing);
+        existing.updated_at = now_timestamp();
+        Ok(existing.clone())
+    }).await
+}
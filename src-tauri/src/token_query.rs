@@ -0,0 +1,392 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use tauri::State;
+
+use crate::config::{load_config, update_config};
+use crate::error::{AppError, AppResult};
+use crate::lifecycle::BanStatus;
+use crate::token_manager::{parse_timestamp, TokenRecord};
+use crate::token_store::TokenStore;
+
+/// 过滤条件，所有字段均可省略，多个条件之间为「且」的关系
+/// 时间边界支持 RFC 3339、`YYYY-MM-DD HH:MM:SS` 和 `YYYY-MM-DD`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct TokenFilter {
+    pub ban_status: Option<BanStatus>,
+    pub tag_name: Option<String>,
+    pub tenant_url: Option<String>,
+    /// 邮箱子串，不区分大小写
+    pub email: Option<String>,
+    pub credits_min: Option<i32>,
+    pub credits_max: Option<i32>,
+    pub expiry_before: Option<String>,
+    pub expiry_after: Option<String>,
+    pub skip_check: Option<bool>,
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub updated_after: Option<String>,
+    pub updated_before: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    CreatedAt,
+    UpdatedAt,
+    Email,
+    CreditsBalance,
+    ExpiryDate,
+    TagName,
+    BanStatus,
+    TenantUrl,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+/// 查询参数；sort 为空时保持存储顺序，limit 为空时返回 offset 之后的全部记录
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TokenQuery {
+    pub filter: TokenFilter,
+    pub sort: Vec<SortKey>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenPage {
+    pub tokens: Vec<TokenRecord>,
+    /// 过滤后（分页前）的记录总数
+    pub total: usize,
+}
+
+/// 保存在配置中的命名过滤条件
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SavedFilter {
+    pub name: String,
+    #[serde(default)]
+    pub filter: TokenFilter,
+    #[serde(default)]
+    pub sort: Vec<SortKey>,
+}
+
+/// 时间区间 [after, before)，边界为空表示不限制
+#[derive(Default)]
+struct TimeRange {
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    fn parse(after: &Option<String>, before: &Option<String>) -> AppResult<Self> {
+        Ok(Self {
+            after: after.as_deref().map(parse_bound).transpose()?,
+            before: before.as_deref().map(parse_bound).transpose()?,
+        })
+    }
+
+    fn is_unbounded(&self) -> bool {
+        self.after.is_none() && self.before.is_none()
+    }
+
+    /// 有边界时，缺失或无法解析的时间视为不匹配
+    fn contains(&self, value: Option<&str>) -> bool {
+        if self.is_unbounded() {
+            return true;
+        }
+        let Some(time) = value.and_then(parse_timestamp) else {
+            return false;
+        };
+        self.after.is_none_or(|after| time >= after) && self.before.is_none_or(|before| time < before)
+    }
+}
+
+fn parse_bound(value: &str) -> AppResult<DateTime<Utc>> {
+    parse_timestamp(value)
+        .ok_or_else(|| AppError::invalid_input(format!("无法识别的时间: {}", value)))
+}
+
+/// 预先解析好时间边界的过滤器
+pub struct TokenMatcher<'a> {
+    filter: &'a TokenFilter,
+    email: Option<String>,
+    expiry: TimeRange,
+    created: TimeRange,
+    updated: TimeRange,
+}
+
+impl<'a> TokenMatcher<'a> {
+    pub fn new(filter: &'a TokenFilter) -> AppResult<Self> {
+        Ok(Self {
+            filter,
+            email: filter.email.as_deref()
+                .map(str::trim)
+                .filter(|email| !email.is_empty())
+                .map(str::to_lowercase),
+            expiry: TimeRange::parse(&filter.expiry_after, &filter.expiry_before)?,
+            created: TimeRange::parse(&filter.created_after, &filter.created_before)?,
+            updated: TimeRange::parse(&filter.updated_after, &filter.updated_before)?,
+        })
+    }
+
+    pub fn matches(&self, token: &TokenRecord) -> bool {
+        let filter = self.filter;
+
+        if filter.ban_status.is_some_and(|status| status != token.ban_status) {
+            return false;
+        }
+        if filter.tag_name.as_ref().is_some_and(|tag| token.tag_name.as_ref() != Some(tag)) {
+            return false;
+        }
+        if filter.tenant_url.as_ref().is_some_and(|url| &token.tenant_url != url) {
+            return false;
+        }
+        if filter.skip_check.is_some_and(|skip| token.skip_check != skip) {
+            return false;
+        }
+
+        if let Some(email) = &self.email {
+            let matched = token.email_note.as_ref()
+                .is_some_and(|note| note.to_lowercase().contains(email));
+            if !matched {
+                return false;
+            }
+        }
+
+        if filter.credits_min.is_some() || filter.credits_max.is_some() {
+            let Some(balance) = credits_balance(token) else {
+                return false;
+            };
+            if filter.credits_min.is_some_and(|min| balance < min)
+                || filter.credits_max.is_some_and(|max| balance > max)
+            {
+                return false;
+            }
+        }
+
+        self.expiry.contains(expiry_date(token))
+            && self.created.contains(Some(&token.created_at))
+            && self.updated.contains(Some(&token.updated_at))
+    }
+}
+
+fn credits_balance(token: &TokenRecord) -> Option<i32> {
+    token.portal_info.as_ref().and_then(|info| info.credits_balance)
+}
+
+fn expiry_date(token: &TokenRecord) -> Option<&str> {
+    token.portal_info.as_ref().and_then(|info| info.expiry_date.as_deref())
+}
+
+/// 排序用的字段值；同一字段总是同一种变体
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum SortValue {
+    Time(DateTime<Utc>),
+    Number(i32),
+    Text(String),
+}
+
+fn sort_value(token: &TokenRecord, field: SortField) -> Option<SortValue> {
+    let text = |value: Option<&String>| {
+        value.filter(|value| !value.is_empty())
+            .map(|value| SortValue::Text(value.to_lowercase()))
+    };
+
+    match field {
+        SortField::CreatedAt => parse_timestamp(&token.created_at).map(SortValue::Time),
+        SortField::UpdatedAt => parse_timestamp(&token.updated_at).map(SortValue::Time),
+        SortField::ExpiryDate => expiry_date(token).and_then(parse_timestamp).map(SortValue::Time),
+        SortField::CreditsBalance => credits_balance(token).map(SortValue::Number),
+        SortField::Email => text(token.email_note.as_ref()),
+        SortField::TagName => text(token.tag_name.as_ref()),
+        SortField::BanStatus => Some(SortValue::Text(token.ban_status.as_str().to_string())),
+        SortField::TenantUrl => text(Some(&token.tenant_url)),
+    }
+}
+
+/// 按多个排序键比较，缺失的值无论升降序都排在最后
+fn compare(a: &TokenRecord, b: &TokenRecord, keys: &[SortKey]) -> Ordering {
+    for key in keys {
+        let ordering = match (sort_value(a, key.field), sort_value(b, key.field)) {
+            (Some(a), Some(b)) => match key.order {
+                SortOrder::Asc => a.cmp(&b),
+                SortOrder::Desc => b.cmp(&a),
+            },
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// 筛选出满足条件的记录（保持原有顺序）
+pub fn filter_tokens(tokens: Vec<TokenRecord>, filter: &TokenFilter) -> AppResult<Vec<TokenRecord>> {
+    let matcher = TokenMatcher::new(filter)?;
+    Ok(tokens.into_iter().filter(|token| matcher.matches(token)).collect())
+}
+
+/// 对记录执行过滤、排序和分页
+pub fn run_query(tokens: Vec<TokenRecord>, query: &TokenQuery) -> AppResult<TokenPage> {
+    let mut matched = filter_tokens(tokens, &query.filter)?;
+
+    if !query.sort.is_empty() {
+        matched.sort_by(|a, b| compare(a, b, &query.sort));
+    }
+
+    let total = matched.len();
+    let tokens = matched.into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok(TokenPage { tokens, total })
+}
+
+/// 在后端查询 tokens：过滤、排序并分页
+#[tauri::command]
+pub async fn query_tokens(store: State<'_, TokenStore>, query: TokenQuery) -> AppResult<TokenPage> {
+    store.query(&query).await
+}
+
+/// 获取已保存的过滤条件
+#[tauri::command]
+pub fn list_saved_filters() -> AppResult<Vec<SavedFilter>> {
+    Ok(load_config()?.saved_filters)
+}
+
+/// 保存过滤条件，同名时覆盖
+#[tauri::command]
+pub fn save_filter(filter: SavedFilter) -> AppResult<()> {
+    let name = filter.name.trim();
+    if name.is_empty() {
+        return Err(AppError::invalid_input("过滤条件名称不能为空"));
+    }
+
+    // 校验时间边界，避免保存无法使用的条件
+    TokenMatcher::new(&filter.filter)?;
+
+    let filter = SavedFilter { name: name.to_string(), ..filter };
+    update_config(|config| {
+        match config.saved_filters.iter_mut().find(|saved| saved.name == filter.name) {
+            Some(saved) => *saved = filter,
+            None => config.saved_filters.push(filter),
+        }
+        Ok(())
+    })
+}
+
+/// 删除已保存的过滤条件
+#[tauri::command]
+pub fn delete_saved_filter(name: String) -> AppResult<()> {
+    update_config(|config| {
+        let before = config.saved_filters.len();
+        config.saved_filters.retain(|saved| saved.name != name);
+
+        if config.saved_filters.len() == before {
+            return Err(AppError::not_found(format!("未找到过滤条件: {}", name)));
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_manager::PortalInfo;
+
+    fn token(id: &str, email: Option<&str>, credits: Option<i32>, created_at: &str) -> TokenRecord {
+        TokenRecord {
+            created_at: created_at.to_string(),
+            email_note: email.map(str::to_string),
+            portal_info: credits.map(|balance| PortalInfo { credits_balance: Some(balance), expiry_date: None }),
+            ..TokenRecord::test(id, &format!("session-{}", id))
+        }
+    }
+
+    fn ids(tokens: &[TokenRecord]) -> Vec<&str> {
+        tokens.iter().map(|token| token.id.as_str()).collect()
+    }
+
+    #[test]
+    fn parse_timestamp_accepts_supported_formats() {
+        let expected = "2024-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(parse_timestamp("2024-03-01T08:00:00+08:00"), Some(expected));
+        assert_eq!(parse_timestamp(" 2024-03-01 00:00:00 "), Some(expected));
+        assert_eq!(parse_timestamp("2024-03-01"), Some(expected));
+        assert_eq!(parse_timestamp("03/01/2024"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+
+    #[test]
+    fn filter_combines_conditions() {
+        let tokens = vec![
+            token("a", Some("Alice@Example.com"), Some(100), "2024-01-01T00:00:00Z"),
+            token("b", Some("bob@example.com"), Some(5), "2024-02-01T00:00:00Z"),
+            token("c", None, None, "2024-03-01T00:00:00Z"),
+        ];
+
+        let filter = TokenFilter { email: Some(" ALICE ".to_string()), ..Default::default() };
+        assert_eq!(ids(&filter_tokens(tokens.clone(), &filter).unwrap()), vec!["a"]);
+
+        // 有积分条件时缺少积分的记录不匹配
+        let filter = TokenFilter { credits_max: Some(50), ..Default::default() };
+        assert_eq!(ids(&filter_tokens(tokens.clone(), &filter).unwrap()), vec!["b"]);
+
+        // 时间区间为 [after, before)
+        let filter = TokenFilter {
+            created_after: Some("2024-02-01".to_string()),
+            created_before: Some("2024-03-01".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&filter_tokens(tokens.clone(), &filter).unwrap()), vec!["b"]);
+
+        let filter = TokenFilter { created_after: Some("last week".to_string()), ..Default::default() };
+        assert!(filter_tokens(tokens, &filter).is_err());
+    }
+
+    #[test]
+    fn sort_puts_missing_values_last_and_paginates() {
+        let tokens = vec![
+            token("a", Some("b@example.com"), Some(100), "2024-01-01T00:00:00Z"),
+            token("b", None, Some(5), "2024-02-01T00:00:00Z"),
+            token("c", Some("a@example.com"), None, "2024-03-01T00:00:00Z"),
+            token("d", Some("a@example.com"), Some(7), "2024-04-01T00:00:00Z"),
+        ];
+
+        let sort = |field, order| vec![SortKey { field, order }];
+        let query = TokenQuery { sort: sort(SortField::CreditsBalance, SortOrder::Desc), ..Default::default() };
+        assert_eq!(ids(&run_query(tokens.clone(), &query).unwrap().tokens), vec!["a", "d", "b", "c"]);
+
+        let query = TokenQuery {
+            sort: vec![
+                SortKey { field: SortField::Email, order: SortOrder::Asc },
+                SortKey { field: SortField::CreatedAt, order: SortOrder::Desc },
+            ],
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = run_query(tokens, &query).unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(ids(&page.tokens), vec!["c", "a"]);
+    }
+}
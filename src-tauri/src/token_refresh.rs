@@ -0,0 +1,177 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinSet;
+
+use crate::error::{AppError, AppResult};
+use crate::http_client::HostRateLimiter;
+use crate::lifecycle;
+use crate::token_manager::{generate_id, TokenRecord};
+use crate::token_store::TokenStore;
+
+/// 默认同时刷新的记录数
+const DEFAULT_CONCURRENCY: usize = 5;
+const MAX_CONCURRENCY: usize = 32;
+
+/// 每条记录刷新完成（成功、失败或取消）后发出的事件
+pub const REFRESH_PROGRESS_EVENT: &str = "refresh-progress";
+
+/// 整个任务结束后发出的事件
+pub const REFRESH_FINISHED_EVENT: &str = "refresh-finished";
+
+/// 运行中的任务：任务 id -> 取消信号
+static JOBS: Mutex<BTreeMap<String, watch::Sender<bool>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshStatus {
+    Refreshed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RefreshProgress {
+    pub job_id: String,
+    pub id: String,
+    pub status: RefreshStatus,
+    /// 刷新后的记录；检查失败时记录仍会更新检查结果
+    pub token: Option<TokenRecord>,
+    pub error: Option<AppError>,
+    pub completed: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RefreshFinished {
+    pub job_id: String,
+    pub refreshed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+}
+
+fn lock_jobs() -> std::sync::MutexGuard<'static, BTreeMap<String, watch::Sender<bool>>> {
+    JOBS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 刷新单条记录；收到取消信号时放弃等待和进行中的请求
+async fn refresh_one(
+    app: AppHandle,
+    id: String,
+    semaphore: Arc<Semaphore>,
+    mut cancel: watch::Receiver<bool>,
+) -> (String, Option<AppResult<TokenRecord>>) {
+    let store = app.state::<TokenStore>();
+    let limiter = app.state::<HostRateLimiter>();
+    let outcome = tokio::select! {
+        biased;
+        _ = cancel.wait_for(|cancelled| *cancelled) => None,
+        result = async {
+            let _permit = semaphore.acquire().await;
+            lifecycle::check_record(&store, &id, &limiter).await
+        } => Some(result),
+    };
+    (id, outcome)
+}
+
+async fn run_job(
+    app: AppHandle,
+    job_id: String,
+    ids: Vec<String>,
+    concurrency: usize,
+    cancel: watch::Receiver<bool>,
+) {
+    let total = ids.len();
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+
+    let mut tasks = JoinSet::new();
+    for id in ids {
+        tasks.spawn(refresh_one(app.clone(), id, semaphore.clone(), cancel.clone()));
+    }
+
+    let mut finished = RefreshFinished { job_id: job_id.clone(), refreshed: 0, failed: 0, cancelled: 0 };
+    let mut completed = 0;
+    while let Some(joined) = tasks.join_next().await {
+        let (id, outcome) = match joined {
+            Ok(joined) => joined,
+            Err(e) => {
+                log::error!(job_id = job_id.as_str(); "刷新任务异常退出: {}", e);
+                continue;
+            }
+        };
+        completed += 1;
+
+        let (status, token, error) = match outcome {
+            None => (RefreshStatus::Cancelled, None, None),
+            Some(Ok(token)) => (RefreshStatus::Refreshed, Some(token), None),
+            Some(Err(e)) => {
+                log::warn!(job_id = job_id.as_str(), id = id.as_str(); "刷新失败: {}", e);
+                // 检查结果已写入记录，把最新的记录一并带给前端
+                let store = app.state::<TokenStore>();
+                let token = store.get(&id).await.ok().flatten();
+                (RefreshStatus::Failed, token, Some(e))
+            }
+        };
+        match status {
+            RefreshStatus::Refreshed => finished.refreshed += 1,
+            RefreshStatus::Failed => finished.failed += 1,
+            RefreshStatus::Cancelled => finished.cancelled += 1,
+        }
+
+        let progress = RefreshProgress { job_id: job_id.clone(), id, status, token, error, completed, total };
+        if let Err(e) = app.emit(REFRESH_PROGRESS_EVENT, progress) {
+            log::warn!("发送刷新进度失败: {}", e);
+        }
+    }
+
+    lock_jobs().remove(&job_id);
+    log::info!(
+        job_id = job_id.as_str(),
+        refreshed = finished.refreshed,
+        failed = finished.failed,
+        cancelled = finished.cancelled;
+        "批量刷新完成"
+    );
+    if let Err(e) = app.emit(REFRESH_FINISHED_EVENT, finished) {
+        log::warn!("发送刷新完成事件失败: {}", e);
+    }
+}
+
+/// 在后台批量重新解析记录的 Session，立即返回任务 id
+/// 同时最多刷新 concurrency 条（默认 5），请求频率由全局的 HostRateLimiter 按主机限制
+/// 每条记录完成后发出 refresh-progress 事件，全部结束后发出 refresh-finished 事件
+#[tauri::command]
+pub async fn refresh_tokens(
+    app: AppHandle,
+    ids: Vec<String>,
+    concurrency: Option<usize>,
+) -> AppResult<String> {
+    if ids.is_empty() {
+        return Err(AppError::invalid_input("未选择要刷新的记录"));
+    }
+
+    let concurrency = concurrency.unwrap_or(DEFAULT_CONCURRENCY).clamp(1, MAX_CONCURRENCY);
+
+    let job_id = generate_id();
+    let (cancel_tx, cancel_rx) = watch::channel(false);
+    lock_jobs().insert(job_id.clone(), cancel_tx);
+
+    log::info!(job_id = job_id.as_str(), count = ids.len(), concurrency = concurrency; "开始批量刷新");
+    tauri::async_runtime::spawn(run_job(app, job_id.clone(), ids, concurrency, cancel_rx));
+
+    Ok(job_id)
+}
+
+/// 取消运行中的任务：尚未开始的记录不再刷新，进行中的请求被中止
+/// 已完成的记录不会回滚
+#[tauri::command]
+pub fn cancel_job(job_id: String) -> AppResult<()> {
+    let jobs = lock_jobs();
+    let cancel = jobs.get(&job_id)
+        .ok_or_else(|| AppError::not_found("任务不存在或已结束"))?;
+    cancel.send_replace(true);
+    log::info!(job_id = job_id.as_str(); "已取消批量刷新");
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::lifecycle::BanStatus;
+use crate::token_manager::TokenRecord;
+use crate::tombstone::Tombstone;
+use crate::trash::TrashedToken;
+
+/// 当前 tokens 存储结构版本
+/// - v0: 裸数组 `[TokenRecord, ...]`（旧版本格式）
+/// - v1: `{ "version": 1, "tokens": [TokenRecord, ...] }`
+/// - v2: ban_status 统一为标准枚举值，新增 last_check 和 status_transitions
+/// - v3: 新增回收站 `trash: [TrashedToken, ...]`
+/// - v4: 新增删除标记 `tombstones: [Tombstone, ...]`
+pub const CURRENT_SCHEMA_VERSION: u32 = 4;
+
+/// 迁移函数：接收版本 N 的完整文档，返回版本 N+1 的文档
+type Migration = fn(Value) -> AppResult<Value>;
+
+/// 迁移链，下标 N 对应 vN -> vN+1
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1, migrate_v1_to_v2, migrate_v2_to_v3, migrate_v3_to_v4];
+
+/// 存储的全部数据：有效记录、回收站与删除标记
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct StoreData {
+    pub tokens: Vec<TokenRecord>,
+    #[serde(default)]
+    pub trash: Vec<TrashedToken>,
+    #[serde(default)]
+    pub tombstones: Vec<Tombstone>,
+}
+
+/// 带版本号的存储文件
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenStoreFile {
+    pub version: u32,
+    #[serde(flatten)]
+    pub data: StoreData,
+}
+
+/// 识别文档的结构版本
+fn detect_version(value: &Value) -> AppResult<u32> {
+    match value {
+        Value::Array(_) => Ok(0),
+        Value::Object(map) => map.get("version")
+            .and_then(Value::as_u64)
+            .map(|version| version as u32)
+            .ok_or_else(|| AppError::new(ErrorCode::Parse, "无法识别 tokens 文件结构: 缺少 version 字段")),
+        _ => Err(AppError::new(ErrorCode::Parse, "无法识别 tokens 文件结构")),
+    }
+}
+
+/// 解析任意版本的 tokens 文件内容，依次执行迁移升级到当前版本
+/// 返回 (文件原始版本, 存储数据)
+pub fn load(content: &str) -> AppResult<(u32, StoreData)> {
+    let mut value: Value = serde_json::from_str(content)
+        .map_err(|e| AppError::parse("解析 tokens 文件失败", e))?;
+
+    let original_version = detect_version(&value)?;
+    if original_version > CURRENT_SCHEMA_VERSION {
+        return Err(AppError::invalid_state(format!(
+            "tokens 文件版本 ({}) 高于当前支持的版本 ({})，请升级应用",
+            original_version, CURRENT_SCHEMA_VERSION
+        )));
+    }
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(original_version as usize) {
+        value = migration(value)
+            .map_err(|e| AppError::new(e.code, format!("迁移 tokens 文件 v{} -> v{} 失败", version, version + 1))
+                .with_details(e.message))?;
+    }
+
+    let file: TokenStoreFile = serde_json::from_value(value)
+        .map_err(|e| AppError::parse("解析 tokens 文件失败", e))?;
+
+    Ok((original_version, file.data))
+}
+
+/// 序列化为当前版本的存储结构
+pub fn dump(data: &StoreData) -> AppResult<String> {
+    serde_json::to_string_pretty(&json!({
+        "version": CURRENT_SCHEMA_VERSION,
+        "tokens": data.tokens,
+        "trash": data.trash,
+        "tombstones": data.tombstones,
+    }))
+    .map_err(|e| AppError::parse("序列化 tokens 失败", e))
+}
+
+/// v0 -> v1：裸数组包装为信封结构，并补齐旧记录中缺失的字段
+fn migrate_v0_to_v1(value: Value) -> AppResult<Value> {
+    let Value::Array(records) = value else {
+        return Err(AppError::new(ErrorCode::Parse, "v0 文件应为数组"));
+    };
+
+    let tokens = records.into_iter()
+        .map(|record| match record {
+            Value::Object(mut map) => {
+                fill_v1_defaults(&mut map);
+                Ok(Value::Object(map))
+            }
+            _ => Err(AppError::new(ErrorCode::Parse, "记录不是对象")),
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    Ok(json!({ "version": 1, "tokens": tokens }))
+}
+
+/// 为缺失的字段填充默认值，与远端导入的默认值保持一致
+fn fill_v1_defaults(map: &mut Map<String, Value>) {
+    let created_at = map.get("created_at").cloned().unwrap_or(Value::String(String::new()));
+
+    let defaults = [
+        ("tenant_url", Value::String(String::new())),
+        ("access_token", Value::String(String::new())),
+        ("updated_at", created_at),
+        ("ban_status", Value::String("ACTIVE".to_string())),
+        ("skip_check", Value::Bool(false)),
+    ];
+    for (key, default) in defaults {
+        map.entry(key).or_insert(default);
+    }
+
+    for key in ["portal_url", "portal_info", "email_note", "tag_name", "tag_color", "suspensions", "balance_color_mode"] {
+        map.entry(key).or_insert(Value::Null);
+    }
+}
+
+/// v1 -> v2：旧的自由文本 ban_status 转换为标准值，补齐生命周期字段
+fn migrate_v1_to_v2(mut value: Value) -> AppResult<Value> {
+    let tokens = value.get_mut("tokens")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| AppError::new(ErrorCode::Parse, "v1 文件缺少 tokens 数组"))?;
+
+    for record in tokens.iter_mut() {
+        let Value::Object(map) = record else {
+            return Err(AppError::new(ErrorCode::Parse, "记录不是对象"));
+        };
+
+        let status = map.get("ban_status")
+            .and_then(Value::as_str)
+            .map(BanStatus::parse)
+            .unwrap_or_default();
+        map.insert("ban_status".to_string(), Value::String(status.as_str().to_string()));
+
+        map.entry("last_check").or_insert(Value::Null);
+        map.entry("status_transitions").or_insert(Value::Array(vec![]));
+    }
+
+    value["version"] = json!(2);
+    Ok(value)
+}
+
+/// v2 -> v3：新增空的回收站
+fn migrate_v2_to_v3(mut value: Value) -> AppResult<Value> {
+    let Value::Object(map) = &mut value else {
+        return Err(AppError::new(ErrorCode::Parse, "v2 文件应为对象"));
+    };
+
+    map.entry("trash").or_insert(Value::Array(vec![]));
+    map.insert("version".to_string(), json!(3));
+    Ok(value)
+}
+
+/// v3 -> v4：新增空的删除标记列表
+fn migrate_v3_to_v4(mut value: Value) -> AppResult<Value> {
+    let Value::Object(map) = &mut value else {
+        return Err(AppError::new(ErrorCode::Parse, "v3 文件应为对象"));
+    };
+
+    map.entry("tombstones").or_insert(Value::Array(vec![]));
+    map.insert("version".to_string(), json!(4));
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v0_array_is_migrated_with_defaults() {
+        let content = json!([{
+            "id": "a",
+            "auth_session": "session-a",
+            "created_at": "2024-01-01T00:00:00Z",
+            "ban_status": "blocked",
+        }]).to_string();
+
+        let (version, data) = load(&content).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(data.tokens.len(), 1);
+
+        let token = &data.tokens[0];
+        assert_eq!(token.updated_at, "2024-01-01T00:00:00Z");
+        assert_eq!(token.tenant_url, "");
+        assert!(!token.skip_check);
+        assert_eq!(token.ban_status, BanStatus::Banned);
+        assert!(token.last_check.is_none());
+        assert!(token.status_transitions.is_empty());
+        assert!(data.trash.is_empty());
+        assert!(data.tombstones.is_empty());
+    }
+
+    #[test]
+    fn v1_free_text_status_is_normalized() {
+        let mut record = json!({
+            "id": "a",
+            "auth_session": "session-a",
+            "created_at": "2024-01-01T00:00:00Z",
+            "ban_status": "Session Invalid",
+        });
+        fill_v1_defaults(record.as_object_mut().unwrap());
+
+        let migrated = migrate_v1_to_v2(json!({ "version": 1, "tokens": [record] })).unwrap();
+        assert_eq!(migrated["version"], 2);
+        assert_eq!(migrated["tokens"][0]["ban_status"], "SESSION_EXPIRED");
+        assert_eq!(migrated["tokens"][0]["status_transitions"], json!([]));
+    }
+
+    #[test]
+    fn dump_and_load_round_trip() {
+        let (_, data) = load(&json!([{
+            "id": "a",
+            "auth_session": "session-a",
+            "created_at": "2024-01-01T00:00:00Z",
+        }]).to_string()).unwrap();
+
+        let (version, loaded) = load(&dump(&data).unwrap()).unwrap();
+        assert_eq!(version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(loaded, data);
+    }
+
+    #[test]
+    fn newer_and_unknown_files_are_rejected() {
+        let newer = json!({ "version": CURRENT_SCHEMA_VERSION + 1, "tokens": [] }).to_string();
+        assert_eq!(load(&newer).unwrap_err().code, ErrorCode::InvalidState);
+
+        assert_eq!(load(r#"{ "tokens": [] }"#).unwrap_err().code, ErrorCode::Parse);
+        assert_eq!(load("42").unwrap_err().code, ErrorCode::Parse);
+    }
+
+    #[test]
+    fn migration_failure_names_the_step() {
+        let error = load(r#"[1]"#).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Parse);
+        assert!(error.message.contains("v0 -> v1"));
+    }
+}
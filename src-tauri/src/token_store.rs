@@ -0,0 +1,523 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use tokio::sync::Mutex;
+
+use crate::config::StorageBackend;
+use crate::error::{AppError, AppResult};
+use crate::storage;
+use crate::token_db;
+use crate::token_manager::{get_tokens_file_path, TokenRecord};
+use crate::token_history::{self, ChangeSource};
+use crate::token_query::{self, TokenPage, TokenQuery};
+use crate::token_schema::{self, StoreData};
+use crate::tombstone;
+use crate::trash::{self, TrashedToken};
+use crate::vault::{self, UnlockedVault, VaultFile};
+
+/// Token 存储服务（由 Tauri 管理的全局状态）
+/// 所有记录缓存在内存中，读写都经过同一把锁串行执行，
+/// 只有本服务会把数据写回磁盘，避免并发的读-改-写互相覆盖
+#[derive(Default)]
+pub struct TokenStore {
+    state: Mutex<StoreState>,
+}
+
+#[derive(Default)]
+struct StoreState {
+    /// 存储后端；首次访问时从配置读取，之后只通过 switch_backend 修改
+    backend: Option<StorageBackend>,
+    /// 已加载的数据；None 表示尚未从磁盘加载
+    cache: Option<LoadedStore>,
+}
+
+struct LoadedStore {
+    backend: StorageBackend,
+    /// 加载时磁盘上的结构版本
+    schema_version: u32,
+    data: StoreData,
+    /// 已落盘的记录（id -> (位置, 记录)），修改后只与它比较并复制发生变化的记录
+    committed: HashMap<String, (usize, TokenRecord)>,
+}
+
+impl StoreState {
+    /// 获取缓存；未加载时从磁盘加载
+    fn loaded(&mut self) -> AppResult<&mut LoadedStore> {
+        let backend = match self.backend {
+            Some(backend) => backend,
+            None => *self.backend.insert(crate::config::load_config()?.storage_backend),
+        };
+
+        if self.cache.is_none() {
+            let (schema_version, data) = load_from_disk(backend)?;
+            let committed = committed_records(&data.tokens);
+            self.cache = Some(LoadedStore { backend, schema_version, data, committed });
+        }
+
+        Ok(self.cache.as_mut().unwrap())
+    }
+}
+
+fn committed_records(tokens: &[TokenRecord]) -> HashMap<String, (usize, TokenRecord)> {
+    tokens.iter()
+        .enumerate()
+        .map(|(position, token)| (token.id.clone(), (position, token.clone())))
+        .collect()
+}
+
+impl TokenStore {
+    /// 获取全部记录的快照
+    pub async fn list(&self) -> AppResult<Vec<TokenRecord>> {
+        let mut state = self.state.lock().await;
+        Ok(state.loaded()?.data.tokens.clone())
+    }
+
+    /// 按 id 查找记录；SQLite 后端直接按主键查询数据库
+    pub async fn get(&self, id: &str) -> AppResult<Option<TokenRecord>> {
+        let mut state = self.state.lock().await;
+        let loaded = state.loaded()?;
+        match loaded.backend {
+            StorageBackend::Json => Ok(loaded.data.tokens.iter().find(|token| token.id == id).cloned()),
+            StorageBackend::Sqlite => token_db::find_by_id(&token_db::open()?, id),
+        }
+    }
+
+    /// 按 auth_session 查找记录；SQLite 后端使用索引查询
+    pub async fn find_by_session(&self, session: &str) -> AppResult<Option<TokenRecord>> {
+        let mut state = self.state.lock().await;
+        let loaded = state.loaded()?;
+        match loaded.backend {
+            StorageBackend::Json => Ok(loaded.data.tokens.iter().find(|token| token.auth_session == session).cloned()),
+            StorageBackend::Sqlite => token_db::find_by_session(&token_db::open()?, session),
+        }
+    }
+
+    /// 过滤、排序并分页；SQLite 后端先用索引列在数据库中筛选候选记录
+    pub async fn query(&self, query: &TokenQuery) -> AppResult<TokenPage> {
+        let mut state = self.state.lock().await;
+        let loaded = state.loaded()?;
+        let candidates = match loaded.backend {
+            StorageBackend::Json => loaded.data.tokens.clone(),
+            StorageBackend::Sqlite => token_db::query_candidates(&token_db::open()?, &query.filter)?,
+        };
+        token_query::run_query(candidates, query)
+    }
+
+    /// 获取回收站的快照（按删除时间从旧到新）
+    pub async fn trash(&self) -> AppResult<Vec<TrashedToken>> {
+        let mut state = self.state.lock().await;
+        Ok(state.loaded()?.data.trash.clone())
+    }
+
+    /// 获取全部数据的快照（记录、回收站和删除标记）
+    pub async fn data(&self) -> AppResult<StoreData> {
+        let mut state = self.state.lock().await;
+        Ok(state.loaded()?.data.clone())
+    }
+
+    /// 返回 (后端, 磁盘结构版本, 记录数)
+    pub async fn info(&self) -> AppResult<(StorageBackend, u32, usize)> {
+        let mut state = self.state.lock().await;
+        let loaded = state.loaded()?;
+        Ok((loaded.backend, loaded.schema_version, loaded.data.tokens.len()))
+    }
+
+    /// 在锁内修改记录并写回磁盘，同时按 source 记录每条记录的变更历史
+    /// 从列表中移除的记录会自动移入回收站并留下删除标记
+    pub async fn mutate<T, F>(&self, source: ChangeSource, f: F) -> AppResult<T>
+    where
+        F: FnOnce(&mut Vec<TokenRecord>) -> AppResult<T>,
+    {
+        self.mutate_data(source, |data| f(&mut data.tokens)).await
+    }
+
+    /// 在锁内修改记录和回收站并写回磁盘
+    /// 修改直接作用在缓存上；修改或写盘失败时丢弃缓存，下次访问从磁盘重新加载，保证内存与磁盘一致
+    pub async fn mutate_data<T, F>(&self, source: ChangeSource, f: F) -> AppResult<T>
+    where
+        F: FnOnce(&mut StoreData) -> AppResult<T>,
+    {
+        let retention_days = crate::config::load_config()?
+            .trash_retention_days
+            .unwrap_or(trash::DEFAULT_TRASH_RETENTION_DAYS);
+
+        let mut state = self.state.lock().await;
+        let result = apply_mutation(state.loaded()?, source, retention_days, f);
+        if result.is_err() {
+            state.cache = None;
+        }
+        result
+    }
+
+    /// 清理回收站中超过保留天数的记录
+    pub async fn purge_trash(&self) -> AppResult<()> {
+        self.mutate_data(ChangeSource::Manual, |_| Ok(())).await
+    }
+
+    /// 用指定的保险库密钥重写 tokens.json（启用保险库或修改口令）
+    /// 写盘成功后才把该密钥设为内存中的密钥；整个过程持有存储锁，其他写入不会在中途用旧密钥覆盖文件
+    pub async fn rewrite_with_key(&self, key: UnlockedVault) -> AppResult<()> {
+        let mut state = self.state.lock().await;
+        let loaded = state.loaded()?;
+
+        if loaded.backend != StorageBackend::Json {
+            return Err(AppError::invalid_state("SQLite 存储不支持保险库加密，请先切换回 JSON 存储"));
+        }
+
+        let file = key.seal(token_schema::dump(&loaded.data)?.as_bytes())?;
+        let content = serde_json::to_string_pretty(&file)
+            .map_err(|e| AppError::parse("序列化保险库失败", e))?;
+        write_json_file(&content)?;
+
+        vault::install(key);
+        loaded.schema_version = token_schema::CURRENT_SCHEMA_VERSION;
+
+        Ok(())
+    }
+
+    /// 切换存储后端（保存配置后调用），下次访问时从新的后端加载并迁移数据
+    pub async fn switch_backend(&self, backend: StorageBackend) {
+        let mut state = self.state.lock().await;
+        if state.backend != Some(backend) {
+            state.backend = Some(backend);
+            state.cache = None;
+        }
+    }
+
+    /// 丢弃缓存，下次访问时重新从磁盘加载（例如锁定保险库后）
+    pub async fn invalidate(&self) {
+        self.state.lock().await.cache = None;
+    }
+}
+
+fn current_schema_version(backend: StorageBackend) -> u32 {
+    match backend {
+        StorageBackend::Json => token_schema::CURRENT_SCHEMA_VERSION,
+        StorageBackend::Sqlite => token_db::DB_SCHEMA_VERSION,
+    }
+}
+
+/// 从磁盘加载全部数据，返回 (结构版本, 存储数据)
+fn load_from_disk(backend: StorageBackend) -> AppResult<(u32, StoreData)> {
+    match backend {
+        StorageBackend::Json => read_json_store(),
+        StorageBackend::Sqlite => {
+            let conn = token_db::open()?;
+            let data = StoreData {
+                tokens: token_db::load_all(&conn)?,
+                trash: token_db::load_trash(&conn)?,
+                tombstones: token_db::load_tombstones(&conn)?,
+            };
+            Ok((token_db::schema_version(&conn)?, data))
+        }
+    }
+}
+
+/// 执行一次修改并写盘，返回修改函数的结果
+/// 修改后的记录与已落盘的记录逐条比较，只复制新增、修改和被移除的记录（用于回收站、变更历史和增量写盘），
+/// 回收站和删除标记只记录 (id, 删除时间)
+fn apply_mutation<T, F>(loaded: &mut LoadedStore, source: ChangeSource, retention_days: u32, f: F) -> AppResult<T>
+where
+    F: FnOnce(&mut StoreData) -> AppResult<T>,
+{
+    let old_trash = entry_keys(&loaded.data.trash, |trashed| (&trashed.token.id, &trashed.deleted_at));
+    let old_tombstones = entry_keys(&loaded.data.tombstones, |tombstone| (&tombstone.id, &tombstone.deleted_at));
+
+    let result = f(&mut loaded.data)?;
+
+    let LoadedStore { backend, schema_version, data, committed } = loaded;
+
+    // 被移除的记录按原来的顺序进入回收站；重新出现在列表中的记录（恢复、覆盖写入）从回收站移出
+    let ids: HashSet<&str> = data.tokens.iter().map(|token| token.id.as_str()).collect();
+    let mut removed_ids: Vec<(usize, String)> = committed.iter()
+        .filter(|(id, _)| !ids.contains(id.as_str()))
+        .map(|(id, (position, _))| (*position, id.clone()))
+        .collect();
+    removed_ids.sort();
+    let removed_ids: Vec<String> = removed_ids.into_iter().map(|(_, id)| id).collect();
+    let removed: Vec<TokenRecord> = removed_ids.iter().map(|id| committed[id].1.clone()).collect();
+
+    let upserts: Vec<&TokenRecord> = data.tokens.iter()
+        .filter(|token| committed.get(&token.id).map(|(_, old)| old) != Some(*token))
+        .collect();
+    // 新增或 Session 发生变化的记录，只有它们可能与已有的删除标记匹配
+    let added: Vec<&TokenRecord> = upserts.iter()
+        .copied()
+        .filter(|token| committed.get(&token.id).is_none_or(|(_, old)| old.auth_session != token.auth_session))
+        .collect();
+
+    data.trash.retain(|trashed| !ids.contains(trashed.token.id.as_str()));
+    tombstone::update(&mut data.tombstones, &data.tokens, &added, &removed, |tombstone| {
+        !old_tombstones.contains(&(tombstone.id.clone(), tombstone.deleted_at.clone()))
+    });
+    trash::move_to_trash(&mut data.trash, removed);
+    trash::purge_expired(&mut data.trash, retention_days);
+
+    let (trash_upserts, trash_deletes) = diff_entries(&old_trash, &data.trash, |trashed| (&trashed.token.id, &trashed.deleted_at));
+    let (tombstone_upserts, tombstone_deletes) = diff_entries(&old_tombstones, &data.tombstones, |tombstone| (&tombstone.id, &tombstone.deleted_at));
+    let changes = token_db::Changes {
+        upserts: upserts.clone(),
+        deletes: removed_ids.iter().map(String::as_str).collect(),
+        trash_upserts,
+        trash_deletes,
+        tombstone_upserts,
+        tombstone_deletes,
+    };
+
+    // 没有任何变化时不写盘，也不产生备份
+    if changes.is_empty() {
+        return Ok(result);
+    }
+
+    match backend {
+        StorageBackend::Json => write_json_store(data)?,
+        StorageBackend::Sqlite => token_db::apply_changes(&mut token_db::open()?, &changes)?,
+    }
+    drop(changes);
+
+    // 历史只比较发生变化的记录；数据已落盘，历史写入失败只记录日志
+    let old_records = upserts.iter()
+        .filter_map(|token| committed.get(&token.id))
+        .chain(removed_ids.iter().map(|id| &committed[id]))
+        .map(|(_, token)| token);
+    if let Err(e) = token_history::append(&token_history::diff(old_records, upserts.iter().copied(), source)) {
+        log::error!("写入变更历史失败: {}", e);
+    }
+
+    for id in &removed_ids {
+        committed.remove(id);
+    }
+    for token in upserts {
+        committed.insert(token.id.clone(), (0, token.clone()));
+    }
+    for (position, token) in data.tokens.iter().enumerate() {
+        if let Some(entry) = committed.get_mut(&token.id) {
+            entry.0 = position;
+        }
+    }
+
+    *schema_version = current_schema_version(*backend);
+
+    Ok(result)
+}
+
+/// 回收站和删除标记的条目只会新增、替换或移除，不会原地修改，按 (id, 删除时间) 即可判断变化
+fn entry_keys<T>(items: &[T], key: impl Fn(&T) -> (&String, &String)) -> HashSet<(String, String)> {
+    items.iter()
+        .map(|item| {
+            let (id, deleted_at) = key(item);
+            (id.clone(), deleted_at.clone())
+        })
+        .collect()
+}
+
+/// 返回 (新增或替换的条目, 被移除的 id)
+fn diff_entries<'a, T>(
+    old_keys: &'a HashSet<(String, String)>,
+    new: &'a [T],
+    key: impl Fn(&T) -> (&String, &String),
+) -> (Vec<&'a T>, Vec<&'a str>) {
+    let new_ids: HashSet<&str> = new.iter().map(|item| key(item).0.as_str()).collect();
+
+    let upserts = new.iter()
+        .filter(|item| {
+            let (id, deleted_at) = key(item);
+            !old_keys.contains(&(id.clone(), deleted_at.clone()))
+        })
+        .collect();
+    let deletes = old_keys.iter()
+        .map(|(id, _)| id.as_str())
+        .filter(|id| !new_ids.contains(id))
+        .collect();
+
+    (upserts, deletes)
+}
+
+/// 解析 tokens 文件内容，保险库格式会先用内存中的密钥解密
+/// 旧版本结构会在内存中升级到当前版本，返回 (文件原始版本, 存储数据)
+pub fn decode_tokens(content: &str) -> AppResult<(u32, StoreData)> {
+    let plaintext = match VaultFile::parse(content) {
+        Some(file) => String::from_utf8(vault::open_with_unlocked(&file)?)
+            .map_err(|e| AppError::parse("解密后的内容无效", e))?,
+        None => content.to_string(),
+    };
+
+    token_schema::load(&plaintext)
+}
+
+/// 序列化存储数据（当前版本结构），保险库已解锁时输出加密格式
+fn encode_tokens(data: &StoreData) -> AppResult<String> {
+    let json_string = token_schema::dump(data)?;
+
+    match vault::seal_if_unlocked(json_string.as_bytes())? {
+        Some(file) => serde_json::to_string_pretty(&file)
+            .map_err(|e| AppError::parse("序列化保险库失败", e)),
+        None => Ok(json_string),
+    }
+}
+
+/// 读取 tokens.json 文件及其结构版本
+fn read_json_store() -> AppResult<(u32, StoreData)> {
+    let file_path = get_tokens_file_path()?;
+
+    if !file_path.exists() {
+        // 从 SQLite 切换回 JSON 时，把数据库中的数据迁移回 tokens.json；
+        // 迁移未完成时删除已写入的文件，避免数据库和 tokens.json 同时存在
+        let migrated = token_db::migrate_to_json(|data| storage::atomic_write(&file_path, encode_tokens(data)?.as_bytes()))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&file_path);
+            })?;
+        if let Some(data) = migrated {
+            return Ok((token_schema::CURRENT_SCHEMA_VERSION, data));
+        }
+
+        // 没有可迁移的数据时创建空文件
+        storage::atomic_write(&file_path, encode_tokens(&StoreData::default())?.as_bytes())
+            .map_err(|e| AppError::io("创建 tokens.json 失败", e))?;
+        return Ok((token_schema::CURRENT_SCHEMA_VERSION, StoreData::default()));
+    }
+
+    let content = fs::read_to_string(&file_path)
+        .map_err(|e| AppError::io("读取 tokens.json 失败", e))?;
+
+    decode_tokens(&content)
+}
+
+/// 写入 tokens.json 文件，保险库已解锁时写入加密格式
+fn write_json_store(data: &StoreData) -> AppResult<()> {
+    let file_path = get_tokens_file_path()?;
+
+    // 保险库已启用但处于锁定状态时，拒绝用明文覆盖加密文件
+    if !vault::is_unlocked() && file_path.exists() {
+        let current = fs::read_to_string(&file_path)
+            .map_err(|e| AppError::io("读取 tokens.json 失败", e))?;
+        if VaultFile::parse(&current).is_some() {
+            return Err(AppError::vault_locked());
+        }
+    }
+
+    write_json_file(&encode_tokens(data)?)
+}
+
+/// 备份当前文件，再通过临时文件 + rename 原子替换 tokens.json
+fn write_json_file(content: &str) -> AppResult<()> {
+    let file_path = get_tokens_file_path()?;
+
+    storage::create_backup(&file_path)?;
+
+    storage::atomic_write(&file_path, content.as_bytes())
+        .map_err(|e| AppError::io("写入 tokens.json 失败", e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    async fn set_backend(store: &TokenStore, backend: StorageBackend) {
+        crate::config::update_config(|config| {
+            config.storage_backend = backend;
+            Ok(())
+        }).unwrap();
+        store.switch_backend(backend).await;
+    }
+
+    #[tokio::test]
+    async fn switching_backends_migrates_data_both_ways() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+        let store = TokenStore::default();
+        let json_path = get_tokens_file_path().unwrap();
+        let db_path = json_path.with_file_name("tokens.db");
+
+        store.mutate(ChangeSource::Manual, |tokens| {
+            tokens.extend([TokenRecord::test("a", "session-a"), TokenRecord::test("b", "session-b")]);
+            Ok(())
+        }).await.unwrap();
+        store.mutate(ChangeSource::Manual, |tokens| {
+            tokens.retain(|token| token.id != "b");
+            Ok(())
+        }).await.unwrap();
+        let before = store.data().await.unwrap();
+
+        set_backend(&store, StorageBackend::Sqlite).await;
+        assert_eq!(store.data().await.unwrap(), before);
+        assert!(!json_path.exists());
+        assert!(db_path.exists());
+
+        // 在 SQLite 中的修改同样会迁移回 JSON
+        store.mutate(ChangeSource::Manual, |tokens| {
+            tokens.push(TokenRecord::test("c", "session-c"));
+            Ok(())
+        }).await.unwrap();
+        let before = store.data().await.unwrap();
+
+        set_backend(&store, StorageBackend::Json).await;
+        assert_eq!(store.data().await.unwrap(), before);
+        assert!(json_path.exists());
+        assert!(!db_path.exists());
+        assert!(db_path.with_file_name("tokens.db.migrated").exists());
+
+        set_backend(&store, StorageBackend::Sqlite).await;
+        assert_eq!(store.data().await.unwrap().tokens.len(), 2);
+        set_backend(&store, StorageBackend::Json).await;
+    }
+
+    #[tokio::test]
+    async fn mutation_records_only_touched_records() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+        let store = TokenStore::default();
+
+        store.mutate(ChangeSource::Manual, |tokens| {
+            tokens.extend(["a", "b", "c", "d"].map(|id| TokenRecord::test(id, &format!("session-{}", id))));
+            Ok(())
+        }).await.unwrap();
+        store.mutate(ChangeSource::Manual, |tokens| {
+            tokens.retain(|token| token.id == "b");
+            tokens[0].email_note = Some("b@example.com".to_string());
+            Ok(())
+        }).await.unwrap();
+
+        // 被移除的记录按原来的顺序进入回收站
+        let trashed: Vec<String> = store.trash().await.unwrap().into_iter().map(|trashed| trashed.token.id).collect();
+        assert_eq!(trashed, vec!["a", "c", "d"]);
+
+        let history = crate::token_history::get_token_history("b".to_string()).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(crate::token_history::get_token_history("d".to_string()).await.unwrap().len(), 2);
+
+        // 再次提交相同的数据不产生变更
+        store.mutate(ChangeSource::Manual, |_| Ok(())).await.unwrap();
+        assert_eq!(crate::token_history::get_token_history("b".to_string()).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sqlite_lookups_match_in_memory_results() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+        let store = TokenStore::default();
+
+        store.mutate(ChangeSource::Manual, |tokens| {
+            tokens.extend([
+                TokenRecord { email_note: Some("Alice_1@example.com".to_string()), ..TokenRecord::test("a", "session-a") },
+                TokenRecord { email_note: Some("alice%2@example.com".to_string()), ..TokenRecord::test("b", "session-b") },
+                TokenRecord::test("c", "session-c"),
+            ]);
+            Ok(())
+        }).await.unwrap();
+
+        let query = TokenQuery {
+            filter: crate::token_query::TokenFilter { email: Some("ALICE_".to_string()), ..Default::default() },
+            ..Default::default()
+        };
+        let json_page = store.query(&query).await.unwrap();
+
+        set_backend(&store, StorageBackend::Sqlite).await;
+        assert_eq!(store.get("b").await.unwrap().unwrap().auth_session, "session-b");
+        assert!(store.get("missing").await.unwrap().is_none());
+        assert_eq!(store.find_by_session("session-c").await.unwrap().unwrap().id, "c");
+
+        // LIKE 通配符需要转义，结果与内存中过滤一致
+        let page = store.query(&query).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.tokens, json_page.tokens);
+        set_backend(&store, StorageBackend::Json).await;
+    }
+}
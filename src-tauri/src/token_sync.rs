@@ -0,0 +1,590 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::error::{AppError, AppResult};
+use crate::remote_push;
+use crate::remote_source::{self, HttpMethod, RemoteRequest};
+use crate::storage;
+use crate::token_history::ChangeSource;
+use crate::token_import::{self, ImportError, MergeStrategy};
+use crate::token_manager::{now_timestamp, ImportResult, RemoteTokenRecord};
+use crate::token_store::TokenStore;
+use crate::tombstone::{self, Tombstone};
+
+/// 单次同步最多拉取的页数
+const MAX_PULL_PAGES: u32 = 100;
+
+/// 同步期间持有，避免并发同步同时改写同步状态
+static SYNC_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// 单个远端的同步状态
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct SourceState {
+    /// 下次拉取时使用的游标
+    cursor: Option<String>,
+    last_synced_at: Option<String>,
+    /// 记录 id -> 远端已有版本的内容摘要
+    synced: BTreeMap<String, String>,
+    /// 远端已知的删除标记 id
+    known_tombstones: BTreeSet<String>,
+}
+
+/// 同步状态：远端地址 -> 状态
+type SyncState = BTreeMap<String, SourceState>;
+
+#[derive(Debug, Deserialize)]
+struct PullResponse {
+    #[serde(default)]
+    records: Vec<RemoteTokenRecord>,
+    #[serde(default)]
+    tombstones: Vec<Tombstone>,
+    #[serde(default)]
+    cursor: Option<String>,
+    #[serde(default)]
+    has_more: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncResult {
+    /// 拉取的记录合并到本地的结果
+    pub pulled: ImportResult,
+    /// 因远端删除标记而删除的本地记录数
+    pub deleted: usize,
+    /// 推送到远端的记录数
+    pub pushed: usize,
+    /// 推送到远端的删除标记数
+    pub pushed_tombstones: usize,
+    /// 推送失败的原因；拉取的结果已经保存，下次同步会重新推送
+    pub push_error: Option<AppError>,
+    pub cursor: Option<String>,
+}
+
+/// 获取同步状态文件路径
+/// 路径: <应用数据目录>/sync_state.json
+fn get_sync_state_path() -> AppResult<PathBuf> {
+    Ok(crate::paths::data_dir()?.join("sync_state.json"))
+}
+
+fn load_sync_state() -> AppResult<SyncState> {
+    let path = get_sync_state_path()?;
+    if !path.exists() {
+        return Ok(SyncState::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| AppError::io("读取同步状态失败", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| AppError::parse("解析同步状态失败", e))
+}
+
+fn save_sync_state(state: &SyncState) -> AppResult<()> {
+    let content = serde_json::to_string_pretty(state)
+        .map_err(|e| AppError::parse("序列化同步状态失败", e))?;
+    storage::atomic_write(&get_sync_state_path()?, content.as_bytes())
+}
+
+/// 从游标开始拉取全部变更，返回 (记录, 删除标记, 新游标)
+async fn pull(
+    client: &reqwest::Client,
+    url: &Url,
+    request: &RemoteRequest,
+    cursor: Option<String>,
+) -> AppResult<(Vec<RemoteTokenRecord>, Vec<Tombstone>, Option<String>)> {
+    let mut records = Vec::new();
+    let mut tombstones = Vec::new();
+    let mut cursor = cursor;
+    let mut visited = HashSet::new();
+
+    for _ in 0..MAX_PULL_PAGES {
+        let mut page_url = url.clone();
+        if let Some(since) = &cursor {
+            page_url.query_pairs_mut().append_pair("since", since);
+        }
+
+        let body = remote_source::fetch_json(request.build_with(client, page_url, HttpMethod::Get)).await?;
+        let page = PullResponse::deserialize(&body)
+            .map_err(|e| AppError::parse("解析同步响应失败", e))?;
+
+        records.extend(page.records);
+        tombstones.extend(page.tombstones);
+        if page.cursor.is_some() {
+            cursor = page.cursor;
+        }
+
+        // 游标没有前进时停止，避免重复拉取同一页
+        let advanced = cursor.as_ref().is_some_and(|cursor| visited.insert(cursor.clone()));
+        if !page.has_more || !advanced {
+            return Ok((records, tombstones, cursor));
+        }
+    }
+
+    log::warn!(max_pages = MAX_PULL_PAGES; "已达到最大页数，剩余变更留到下次同步");
+    Ok((records, tombstones, cursor))
+}
+
+/// 与远端双向同步：先拉取并合并远端变更，再推送本地的新增、修改和删除
+/// 拉取：GET <url>?since=<cursor>（首次同步不带 since），响应为
+///   { "records": [...], "tombstones": [...], "cursor": "...", "has_more": false }
+///   records 与导入时的 RemoteTokenRecord 结构一致，tombstones 为 { id, session_hash, deleted_at }，
+///   has_more 为 true 时用新的 cursor 继续拉取
+/// 推送：POST <url>，请求体为 { "records": [...], "tombstones": [...] }，返回 2xx 即视为成功
+/// 冲突按 updated_at 处理，较新的一方获胜；删除标记晚于记录的 updated_at 时删除本地记录
+/// request 只使用其中的请求头
+#[tauri::command]
+pub async fn sync_with_remote(
+    store: State<'_, TokenStore>,
+    api_url: String,
+    request: Option<RemoteRequest>,
+) -> AppResult<SyncResult> {
+    run_sync(&store, &api_url, &request.unwrap_or_default()).await
+}
+
+async fn run_sync(store: &TokenStore, api_url: &str, request: &RemoteRequest) -> AppResult<SyncResult> {
+    let url = Url::parse(api_url)
+        .map_err(|e| AppError::invalid_input("远端 API 地址无效").with_details(e))?;
+
+    let _guard = SYNC_LOCK.lock().await;
+    let mut state = load_sync_state()?;
+    let mut source = state.remove(api_url).unwrap_or_default();
+    let client = crate::http_client::create_client()?;
+
+    log::info!(url = api_url, has_cursor = source.cursor.is_some(); "开始同步");
+
+    // ========== 拉取 ==========
+
+    let (remote_records, remote_tombstones, cursor) = pull(&client, &url, request, source.cursor.clone()).await?;
+
+    let mut incoming = Vec::new();
+    let mut errors: Vec<ImportError> = Vec::new();
+    for (index, record) in remote_records.iter().enumerate() {
+        match record.to_local_token(index, false) {
+            Ok(token) => incoming.push(token),
+            Err(e) => {
+                log::warn!(index = index, excerpt = e.excerpt.as_str(); "同步记录转换失败: {}", e);
+                errors.push(e);
+            }
+        }
+    }
+    // 合并后 updated_at 与远端一致的记录说明远端已有该版本，不需要再推送回去
+    let remote_versions: HashMap<String, String> = incoming.iter()
+        .map(|token| (token.auth_session.clone(), token.updated_at.clone()))
+        .collect();
+
+    let (pulled, deleted) = store.mutate_data(ChangeSource::Sync, |data| {
+        let mut deleted = 0;
+        for remote in &remote_tombstones {
+            let before = data.tokens.len();
+            data.tokens.retain(|token| {
+                let matches = token.id == remote.id || tombstone::session_hash(&token.auth_session) == remote.session_hash;
+                !(matches && tombstone::blocks(remote, token))
+            });
+            deleted += before - data.tokens.len();
+
+            if !data.tombstones.iter().any(|tombstone| tombstone.id == remote.id) {
+                data.tombstones.push(remote.clone());
+            }
+        }
+
+        let actions = token_import::merge_into(&mut data.tokens, &data.tombstones, incoming, MergeStrategy::NewestWins);
+        Ok((ImportResult::from_actions(&actions, errors), deleted))
+    }).await?;
+
+    source.known_tombstones.extend(remote_tombstones.iter().map(|tombstone| tombstone.id.clone()));
+    source.cursor = cursor;
+
+    // ========== 推送 ==========
+
+    let data = store.data().await?;
+    let mut records: Vec<Value> = Vec::new();
+    let mut fingerprints = Vec::new();
+    for token in &data.tokens {
+        let payload = token.to_remote_value()?;
+        let fingerprint = remote_push::fingerprint(&payload);
+        if source.synced.get(&token.id) == Some(&fingerprint) {
+            continue;
+        }
+        if remote_versions.get(&token.auth_session) == Some(&token.updated_at) {
+            source.synced.insert(token.id.clone(), fingerprint);
+            continue;
+        }
+        records.push(payload);
+        fingerprints.push((token.id.clone(), fingerprint));
+    }
+    let tombstones: Vec<&Tombstone> = data.tombstones.iter()
+        .filter(|tombstone| !source.known_tombstones.contains(&tombstone.id))
+        .collect();
+
+    let (pushed, pushed_tombstones) = (records.len(), tombstones.len());
+    let push_error = if pushed == 0 && pushed_tombstones == 0 {
+        None
+    } else {
+        let body = serde_json::json!({ "records": records, "tombstones": tombstones });
+        let response = request.build_with(&client, url.clone(), HttpMethod::Post)
+            .json(&body)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => None,
+            Ok(response) => Some(AppError::http_status("推送同步数据失败", response.status())),
+            Err(e) => Some(AppError::network("推送同步数据失败", e)),
+        }
+    };
+
+    match &push_error {
+        None => {
+            source.synced.extend(fingerprints);
+            source.known_tombstones.extend(tombstones.iter().map(|tombstone| tombstone.id.clone()));
+        }
+        Some(e) => log::warn!(url = api_url; "推送同步数据失败: {}", e),
+    }
+
+    // 已不存在的记录不再需要摘要
+    let ids: HashSet<&str> = data.tokens.iter().map(|token| token.id.as_str()).collect();
+    source.synced.retain(|id, _| ids.contains(id.as_str()));
+
+    source.last_synced_at = Some(now_timestamp());
+    let cursor = source.cursor.clone();
+    state.insert(api_url.to_string(), source);
+    save_sync_state(&state)?;
+
+    let (pushed, pushed_tombstones) = if push_error.is_none() { (pushed, pushed_tombstones) } else { (0, 0) };
+    log::info!(
+        url = api_url,
+        imported = pulled.imported,
+        updated = pulled.updated,
+        deleted = deleted,
+        pushed = pushed,
+        pushed_tombstones = pushed_tombstones;
+        "同步完成"
+    );
+
+    Ok(SyncResult { pulled, deleted, pushed, pushed_tombstones, push_error, cursor })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote_mapping::RemoteMapping;
+    use crate::token_manager::{self, TokenRecord};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex as StdMutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    enum Change {
+        Record(Value),
+        Tombstone(Value),
+    }
+
+    /// 本地模拟的同步服务端：按顺序保存变更，游标即已返回的变更数
+    /// GET /sync?since= 每页最多返回 page_size 条，POST /sync 追加推送的内容，
+    /// GET /import 按原有导入格式返回全部记录
+    struct Remote {
+        changes: Vec<Change>,
+        page_size: usize,
+        /// 每次拉取使用的 since 参数
+        pulls: Vec<Option<String>>,
+        pushes: Vec<Value>,
+    }
+
+    impl Remote {
+        fn new(changes: Vec<Change>) -> Arc<StdMutex<Self>> {
+            Arc::new(StdMutex::new(Self { changes, page_size: 10, pulls: vec![], pushes: vec![] }))
+        }
+
+        fn handle(&mut self, method: &str, target: &str, body: &[u8]) -> Value {
+            let url = Url::parse(&format!("http://localhost{}", target)).unwrap();
+
+            if url.path() == "/import" {
+                let records: Vec<&Value> = self.changes.iter()
+                    .filter_map(|change| match change {
+                        Change::Record(record) => Some(record),
+                        Change::Tombstone(_) => None,
+                    })
+                    .collect();
+                return json!({ "status": 1, "data": records });
+            }
+
+            if method == "POST" {
+                let body: Value = serde_json::from_slice(body).unwrap();
+                for record in body["records"].as_array().unwrap() {
+                    self.changes.push(Change::Record(record.clone()));
+                }
+                for tombstone in body["tombstones"].as_array().unwrap() {
+                    self.changes.push(Change::Tombstone(tombstone.clone()));
+                }
+                self.pushes.push(body);
+                return json!({});
+            }
+
+            let since = url.query_pairs()
+                .find(|(name, _)| name == "since")
+                .map(|(_, value)| value.into_owned());
+            self.pulls.push(since.clone());
+
+            let start: usize = since.as_deref().map(|since| since.parse().unwrap()).unwrap_or(0);
+            let end = (start + self.page_size).min(self.changes.len());
+            let (mut records, mut tombstones) = (vec![], vec![]);
+            for change in &self.changes[start..end] {
+                match change {
+                    Change::Record(record) => records.push(record.clone()),
+                    Change::Tombstone(tombstone) => tombstones.push(tombstone.clone()),
+                }
+            }
+            json!({
+                "records": records,
+                "tombstones": tombstones,
+                "cursor": end.to_string(),
+                "has_more": end < self.changes.len(),
+            })
+        }
+    }
+
+    async fn read_request(socket: &mut TcpStream) -> (String, String, Vec<u8>) {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let header_end = loop {
+            let read = socket.read(&mut chunk).await.unwrap();
+            buffer.extend_from_slice(&chunk[..read]);
+            if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                break position + 4;
+            }
+        };
+
+        let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+        let content_length: usize = head.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .map(|(_, value)| value.trim().parse().unwrap())
+            .unwrap_or(0);
+        while buffer.len() < header_end + content_length {
+            let read = socket.read(&mut chunk).await.unwrap();
+            buffer.extend_from_slice(&chunk[..read]);
+        }
+
+        let mut request_line = head.lines().next().unwrap().split_whitespace();
+        let method = request_line.next().unwrap().to_string();
+        let target = request_line.next().unwrap().to_string();
+        (method, target, buffer[header_end..header_end + content_length].to_vec())
+    }
+
+    /// 启动本地服务端，返回服务地址
+    async fn serve(remote: Arc<StdMutex<Remote>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let remote = remote.clone();
+                tokio::spawn(async move {
+                    let (method, target, body) = read_request(&mut socket).await;
+                    let payload = remote.lock().unwrap().handle(&method, &target, &body).to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        payload.len(),
+                        payload
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    fn record(id: &str, session: &str, updated_at: &str, note: &str) -> Value {
+        json!({
+            "id": id,
+            "tenant_url": "https://tenant.example.com/",
+            "access_token": format!("token-{}", id),
+            "auth_session": session,
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": updated_at,
+            "email_note": note,
+        })
+    }
+
+    /// 与 record 返回的远端记录内容相同的本地记录
+    fn token(id: &str, session: &str, updated_at: &str, note: &str) -> TokenRecord {
+        TokenRecord {
+            tenant_url: "https://tenant.example.com/".to_string(),
+            access_token: format!("token-{}", id),
+            updated_at: updated_at.to_string(),
+            email_note: Some(note.to_string()),
+            ..TokenRecord::test(id, session)
+        }
+    }
+
+    async fn add(store: &TokenStore, tokens: Vec<TokenRecord>) {
+        store.mutate(ChangeSource::Manual, |current| {
+            current.extend(tokens);
+            Ok(())
+        }).await.unwrap();
+    }
+
+    async fn delete(store: &TokenStore, id: &str) {
+        store.mutate(ChangeSource::Manual, |tokens| {
+            tokens.retain(|token| token.id != id);
+            Ok(())
+        }).await.unwrap();
+    }
+
+    async fn import(store: &TokenStore, url: &str) -> ImportResult {
+        token_manager::run_remote_import(
+            store,
+            &format!("{}/import", url),
+            &RemoteRequest::default(),
+            &RemoteMapping::default(),
+            MergeStrategy::NewestWins,
+            false,
+            false,
+        ).await.unwrap()
+    }
+
+    fn note(tokens: &[TokenRecord], id: &str) -> Option<String> {
+        tokens.iter().find(|token| token.id == id).and_then(|token| token.email_note.clone())
+    }
+
+    #[tokio::test]
+    async fn pull_follows_cursor_across_pages() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+        let store = TokenStore::default();
+        let remote = Remote::new(vec![
+            Change::Record(record("a", "session-a", "2024-01-01T00:00:00Z", "a")),
+            Change::Record(record("b", "session-b", "2024-01-01T00:00:00Z", "b")),
+            Change::Record(record("c", "session-c", "2024-01-01T00:00:00Z", "c")),
+        ]);
+        remote.lock().unwrap().page_size = 2;
+        let url = format!("{}/sync", serve(remote.clone()).await);
+
+        let result = run_sync(&store, &url, &RemoteRequest::default()).await.unwrap();
+        assert_eq!(result.pulled.imported, 3);
+        assert_eq!(result.cursor.as_deref(), Some("3"));
+        // 拉取到的记录与远端版本一致，不需要推送
+        assert_eq!(result.pushed, 0);
+
+        remote.lock().unwrap().changes.push(Change::Record(record("d", "session-d", "2024-01-01T00:00:00Z", "d")));
+        let result = run_sync(&store, &url, &RemoteRequest::default()).await.unwrap();
+        assert_eq!(result.pulled.imported, 1);
+        assert_eq!(result.cursor.as_deref(), Some("4"));
+        assert_eq!(store.list().await.unwrap().len(), 4);
+
+        let remote = remote.lock().unwrap();
+        assert_eq!(remote.pulls, vec![None, Some("2".to_string()), Some("3".to_string())]);
+        assert!(remote.pushes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn push_sends_local_records_and_tombstones_once() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+        let store = TokenStore::default();
+        add(&store, vec![
+            token("a", "session-a", "2024-01-01T00:00:00Z", "a"),
+            token("b", "session-b", "2024-01-01T00:00:00Z", "b"),
+        ]).await;
+        delete(&store, "b").await;
+
+        let remote = Remote::new(vec![]);
+        let url = format!("{}/sync", serve(remote.clone()).await);
+
+        let result = run_sync(&store, &url, &RemoteRequest::default()).await.unwrap();
+        assert_eq!((result.pushed, result.pushed_tombstones), (1, 1));
+        assert!(result.push_error.is_none());
+
+        // 推送的内容在下次拉取时回来，不会再次推送
+        let result = run_sync(&store, &url, &RemoteRequest::default()).await.unwrap();
+        assert_eq!((result.pushed, result.pushed_tombstones), (0, 0));
+
+        let remote = remote.lock().unwrap();
+        assert_eq!(remote.pushes.len(), 1);
+        let push = &remote.pushes[0];
+        assert_eq!(push["records"].as_array().unwrap().len(), 1);
+        assert_eq!(push["records"][0]["id"], "a");
+        assert_eq!(push["tombstones"][0]["id"], "b");
+        assert_eq!(push["tombstones"][0]["session_hash"], tombstone::session_hash("session-b"));
+        // 只在本地使用的字段不推送
+        assert!(push["records"][0].get("last_check").is_none());
+    }
+
+    #[tokio::test]
+    async fn newest_updated_at_wins_in_both_directions() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+        let store = TokenStore::default();
+        add(&store, vec![
+            token("a", "session-a", "2024-02-01T00:00:00Z", "local-new"),
+            token("b", "session-b", "2024-01-01T00:00:00Z", "local-old"),
+        ]).await;
+
+        let remote = Remote::new(vec![
+            Change::Record(record("a", "session-a", "2024-01-15T00:00:00Z", "remote-old")),
+            Change::Record(record("b", "session-b", "2024-03-01T00:00:00Z", "remote-new")),
+        ]);
+        let url = format!("{}/sync", serve(remote.clone()).await);
+
+        let result = run_sync(&store, &url, &RemoteRequest::default()).await.unwrap();
+        assert_eq!(result.pulled.updated, 1);
+        assert_eq!(result.pushed, 1);
+
+        let tokens = store.list().await.unwrap();
+        assert_eq!(note(&tokens, "a").as_deref(), Some("local-new"));
+        assert_eq!(note(&tokens, "b").as_deref(), Some("remote-new"));
+
+        let remote = remote.lock().unwrap();
+        let pushed = remote.pushes[0]["records"].as_array().unwrap();
+        assert_eq!(pushed.len(), 1);
+        assert_eq!(pushed[0]["id"], "a");
+        assert_eq!(pushed[0]["email_note"], "local-new");
+    }
+
+    #[tokio::test]
+    async fn deleted_account_is_not_reimported() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+        let store = TokenStore::default();
+        add(&store, vec![token("a", "session-a", "2024-01-01T00:00:00Z", "a")]).await;
+        delete(&store, "a").await;
+
+        let remote = Remote::new(vec![
+            Change::Record(record("other-id", "session-a", "2024-01-01T00:00:00Z", "stale")),
+        ]);
+        let url = serve(remote).await;
+
+        let result = import(&store, &url).await;
+        assert_eq!((result.imported, result.skipped), (0, 1));
+        assert!(store.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn remote_tombstone_matched_by_session_is_not_pushed_back() {
+        let _guard = crate::paths::lock_test_data_dir().await;
+        let store = TokenStore::default();
+        add(&store, vec![token("local-id", "session-a", "2024-01-01T00:00:00Z", "a")]).await;
+
+        let remote = Remote::new(vec![Change::Tombstone(json!({
+            "id": "remote-id",
+            "session_hash": tombstone::session_hash("session-a"),
+            "deleted_at": "2024-06-01T00:00:00Z",
+        }))]);
+        let url = serve(remote.clone()).await;
+
+        let result = run_sync(&store, &format!("{}/sync", url), &RemoteRequest::default()).await.unwrap();
+        assert_eq!(result.deleted, 1);
+        assert_eq!(result.pushed_tombstones, 0);
+        assert!(remote.lock().unwrap().pushes.is_empty());
+
+        let data = store.data().await.unwrap();
+        assert!(data.tokens.is_empty());
+        let ids: Vec<&str> = data.tombstones.iter().map(|tombstone| tombstone.id.as_str()).collect();
+        assert_eq!(ids, vec!["remote-id"]);
+
+        // 远端删除的账号也不会被之后的导入带回
+        remote.lock().unwrap().changes.push(Change::Record(record("local-id", "session-a", "2024-01-01T00:00:00Z", "stale")));
+        let result = import(&store, &url).await;
+        assert_eq!((result.imported, result.skipped), (0, 1));
+        assert!(store.list().await.unwrap().is_empty());
+    }
+}
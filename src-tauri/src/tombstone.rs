@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+use crate::token_manager::{now_timestamp, TokenRecord};
+use crate::token_manager::parse_timestamp;
+
+/// 删除标记：记录被删除后保留 id 和 Session 摘要，
+/// 导入和同步时据此跳过已删除的账号，避免被旧数据重新带回
+/// 只有重新添加相同 id 或 Session 的记录（包括从回收站恢复）才会清除
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Tombstone {
+    pub id: String,
+    /// auth_session 的 SHA-256，不保存 Session 原文
+    pub session_hash: String,
+    pub deleted_at: String,
+}
+
+/// 计算 auth_session 的摘要
+pub fn session_hash(auth_session: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(auth_session.as_bytes());
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// 更新删除标记：清除与新增（或 Session 变化）的记录匹配的标记，为被移除的记录添加标记
+/// 之前已存在的记录在当时已经清除过匹配的标记，不需要重新计算全部 Session 的摘要；
+/// 本次修改中新加入的标记（is_new，例如同步拉取的远端标记）仍与全部记录比较
+pub fn update(
+    tombstones: &mut Vec<Tombstone>,
+    tokens: &[TokenRecord],
+    added: &[&TokenRecord],
+    removed: &[TokenRecord],
+    is_new: impl Fn(&Tombstone) -> bool,
+) {
+    if !tombstones.is_empty() {
+        let check_all = tombstones.iter().any(&is_new);
+        let candidates: Vec<&TokenRecord> = if check_all { tokens.iter().collect() } else { added.to_vec() };
+
+        if !candidates.is_empty() {
+            let ids: HashSet<&str> = candidates.iter().map(|token| token.id.as_str()).collect();
+            let sessions: HashSet<String> = candidates.iter().map(|token| session_hash(&token.auth_session)).collect();
+            tombstones.retain(|tombstone| !ids.contains(tombstone.id.as_str()) && !sessions.contains(&tombstone.session_hash));
+        }
+    }
+
+    if removed.is_empty() {
+        return;
+    }
+
+    let sessions: HashSet<&str> = tokens.iter().map(|token| token.auth_session.as_str()).collect();
+    let deleted_at = now_timestamp();
+    for token in removed {
+        // 同一 Session 仍以其他 id 存在时不算删除账号
+        if sessions.contains(token.auth_session.as_str()) {
+            continue;
+        }
+        // 已有相同 id 或 Session 的标记（例如同步拉取的远端标记删除了本地另一 id 的同一账号）时不再重复标记，
+        // 否则新标记会被当作本地删除推送回远端
+        let hash = session_hash(&token.auth_session);
+        if tombstones.iter().any(|tombstone| tombstone.id == token.id || tombstone.session_hash == hash) {
+            continue;
+        }
+        tombstones.push(Tombstone {
+            id: token.id.clone(),
+            session_hash: hash,
+            deleted_at: deleted_at.clone(),
+        });
+    }
+}
+
+/// 查找与记录对应的删除标记（按 id 或 Session 匹配）
+pub fn find<'a>(tombstones: &'a [Tombstone], token: &TokenRecord) -> Option<&'a Tombstone> {
+    let hash = session_hash(&token.auth_session);
+    tombstones.iter().find(|tombstone| tombstone.id == token.id || tombstone.session_hash == hash)
+}
+
+/// 记录是否因删除标记而不应导入：只有在删除之后更新过的记录才允许重新导入
+pub fn blocks(tombstone: &Tombstone, token: &TokenRecord) -> bool {
+    match (parse_timestamp(&token.updated_at), parse_timestamp(&tombstone.deleted_at)) {
+        (Some(updated_at), Some(deleted_at)) => updated_at <= deleted_at,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn tombstone(id: &str, session: &str, deleted_at: &str) -> Tombstone {
+        Tombstone { id: id.to_string(), session_hash: session_hash(session), deleted_at: deleted_at.to_string() }
+    }
+
+    #[test]
+    fn removed_records_get_one_tombstone() {
+        let mut tombstones = vec![];
+        let kept = TokenRecord::test("b", "s2");
+        update(&mut tombstones, std::slice::from_ref(&kept), &[], &[TokenRecord::test("a", "s1")], |_| false);
+        assert_eq!(tombstones.len(), 1);
+        assert_eq!(tombstones[0].id, "a");
+        assert_ne!(tombstones[0].session_hash, "s1");
+
+        // 已有相同 Session 的标记时不再重复添加
+        update(&mut tombstones, &[kept], &[], &[TokenRecord::test("c", "s1")], |_| false);
+        assert_eq!(tombstones.len(), 1);
+    }
+
+    #[test]
+    fn removing_a_duplicate_of_a_remaining_session_is_not_a_delete() {
+        let mut tombstones = vec![];
+        update(&mut tombstones, &[TokenRecord::test("b", "s1")], &[], &[TokenRecord::test("a", "s1")], |_| false);
+        assert!(tombstones.is_empty());
+    }
+
+    #[test]
+    fn adding_a_matching_record_clears_the_tombstone() {
+        let mut tombstones = vec![tombstone("a", "s1", "2024-02-01T00:00:00Z"), tombstone("x", "s9", "2024-02-01T00:00:00Z")];
+        let restored = TokenRecord::test("other", "s1");
+        update(&mut tombstones, std::slice::from_ref(&restored), &[&restored], &[], |_| false);
+        let ids: Vec<&str> = tombstones.iter().map(|tombstone| tombstone.id.as_str()).collect();
+        assert_eq!(ids, vec!["x"]);
+    }
+
+    #[test]
+    fn new_tombstones_are_checked_against_all_records() {
+        // 远端标记删除的账号仍在本地时立即清除该标记
+        let mut tombstones = vec![tombstone("a", "s1", "2024-02-01T00:00:00Z")];
+        update(&mut tombstones, &[TokenRecord::test("a", "s1")], &[], &[], |_| true);
+        assert!(tombstones.is_empty());
+
+        let mut tombstones = vec![tombstone("a", "s1", "2024-02-01T00:00:00Z")];
+        update(&mut tombstones, &[TokenRecord::test("a", "s1")], &[], &[], |_| false);
+        assert_eq!(tombstones.len(), 1);
+    }
+
+    #[test]
+    fn blocks_only_records_not_updated_after_deletion() {
+        let deleted = tombstone("a", "s1", "2024-02-01T00:00:00Z");
+        let mut record = TokenRecord::test("other", "s1");
+        assert_eq!(find(std::slice::from_ref(&deleted), &record), Some(&deleted));
+        assert!(blocks(&deleted, &record));
+
+        record.updated_at = "2024-03-01T00:00:00Z".to_string();
+        assert!(!blocks(&deleted, &record));
+
+        record.updated_at = "unknown".to_string();
+        assert!(blocks(&deleted, &record));
+
+        assert_eq!(find(&[deleted], &TokenRecord::test("b", "s2")), None);
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tauri::State;
+
+use crate::error::{AppError, AppResult};
+use crate::token_history::ChangeSource;
+use crate::token_manager::{now_timestamp, TokenRecord};
+use crate::token_manager::parse_timestamp;
+use crate::token_store::TokenStore;
+
+/// 回收站记录默认保留天数
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+/// 回收站中的记录
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrashedToken {
+    pub deleted_at: String,
+    pub token: TokenRecord,
+}
+
+/// 把被移除的记录放入回收站；同一 id 再次删除时替换旧条目
+pub fn move_to_trash(trash: &mut Vec<TrashedToken>, removed: Vec<TokenRecord>) {
+    if removed.is_empty() {
+        return;
+    }
+
+    let deleted_at = now_timestamp();
+    let removed_ids: HashSet<&str> = removed.iter().map(|token| token.id.as_str()).collect();
+    trash.retain(|trashed| !removed_ids.contains(trashed.token.id.as_str()));
+
+    trash.extend(removed.into_iter().map(|token| TrashedToken {
+        deleted_at: deleted_at.clone(),
+        token,
+    }));
+}
+
+/// 清理超过保留天数的记录，retention_days 为 0 时不清理；返回清理数量
+pub fn purge_expired(trash: &mut Vec<TrashedToken>, retention_days: u32) -> usize {
+    if retention_days == 0 {
+        return 0;
+    }
+
+    let cutoff = Utc::now() - Duration::days(retention_days as i64);
+    let before = trash.len();
+    // 无法解析删除时间的条目保留，交给用户手动清空
+    trash.retain(|trashed| parse_timestamp(&trashed.deleted_at).is_none_or(|deleted_at| deleted_at > cutoff));
+
+    let purged = before - trash.len();
+    if purged > 0 {
+        log::info!(purged = purged, retention_days = retention_days; "已清理过期的回收站记录");
+    }
+    purged
+}
+
+/// 列出回收站中的记录（按删除时间从新到旧）
+#[tauri::command]
+pub async fn list_trash(store: State<'_, TokenStore>) -> AppResult<Vec<TrashedToken>> {
+    let mut trash = store.trash().await?;
+    trash.reverse();
+    Ok(trash)
+}
+
+/// 从回收站恢复记录
+#[tauri::command]
+pub async fn restore_token(store: State<'_, TokenStore>, id: String) -> AppResult<TokenRecord> {
+    store.mutate_data(ChangeSource::Restore, |data| {
+        let index = data.trash.iter()
+            .position(|trashed| trashed.token.id == id)
+            .ok_or_else(|| AppError::not_found("回收站中未找到该记录"))?;
+
+        let token = &data.trash[index].token;
+        if data.tokens.iter().any(|t| t.id == token.id) {
+            return Err(AppError::duplicate("已存在相同 id 的记录"));
+        }
+        if data.tokens.iter().any(|t| t.auth_session == token.auth_session) {
+            return Err(AppError::duplicate("该 Session 已存在"));
+        }
+
+        let token = data.trash.remove(index).token;
+        data.tokens.push(token.clone());
+        Ok(token)
+    }).await
+}
+
+/// 永久删除回收站中的全部记录，返回删除数量
+#[tauri::command]
+pub async fn empty_trash(store: State<'_, TokenStore>) -> AppResult<usize> {
+    store.mutate_data(ChangeSource::Manual, |data| {
+        let count = data.trash.len();
+        data.trash.clear();
+        Ok(count)
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn trashed(id: &str, deleted_at: &str) -> TrashedToken {
+        TrashedToken { deleted_at: deleted_at.to_string(), token: TokenRecord::test(id, &format!("session-{}", id)) }
+    }
+
+    #[test]
+    fn deleting_the_same_id_again_replaces_the_entry() {
+        let mut trash = vec![trashed("a", "2024-01-01T00:00:00Z"), trashed("b", "2024-01-01T00:00:00Z")];
+        move_to_trash(&mut trash, vec![TokenRecord::test("a", "session-a")]);
+
+        let ids: Vec<&str> = trash.iter().map(|trashed| trashed.token.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+        assert_ne!(trash[1].deleted_at, "2024-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn purge_keeps_recent_and_unparsable_entries() {
+        let recent = (Utc::now() - Duration::days(1)).to_rfc3339();
+        let mut trash = vec![
+            trashed("old", "2000-01-01T00:00:00Z"),
+            trashed("recent", &recent),
+            trashed("unknown", "someday"),
+        ];
+
+        assert_eq!(purge_expired(&mut trash, 0), 0);
+        assert_eq!(trash.len(), 3);
+
+        assert_eq!(purge_expired(&mut trash, DEFAULT_TRASH_RETENTION_DAYS), 1);
+        let ids: Vec<&str> = trash.iter().map(|trashed| trashed.token.id.as_str()).collect();
+        assert_eq!(ids, vec!["recent", "unknown"]);
+    }
+}
@@ -0,0 +1,412 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+
+use crate::error::{self, AppError, AppResult, ErrorCode};
+use crate::storage;
+use crate::token_store::TokenStore;
+use tauri::State;
+
+/// 加密文件格式版本
+const VAULT_FORMAT_VERSION: u32 = 1;
+const KDF_ALGORITHM: &str = "argon2id";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// 当前解锁的密钥（仅保存在内存中）
+static UNLOCKED: Mutex<Option<UnlockedVault>> = Mutex::new(None);
+
+/// Argon2id 参数，随加密文件一起保存，便于以后调整强度
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: String,
+}
+
+/// 加密后的 tokens 文件内容
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VaultFile {
+    pub vault: u32,
+    pub kdf: KdfParams,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// 保险库只加密 tokens.json（包括其中的回收站和删除标记）及其备份，以下文件仍以明文保存
+/// 返回给前端的是文件标识，说明文字由前端显示：
+/// - history: history.jsonl，Session、access_token 和邮箱备注已遮蔽
+/// - config: config.json，定时导入源的请求头可能包含 API 密钥
+/// - sync_state: sync_state.json、push_state.json，只包含记录 id 和内容摘要
+/// - remote_sources: remote_sources.json，定时导入的运行状态和错误信息
+/// - logs: 运行日志，Session 等敏感值已脱敏
+const UNENCRYPTED_FILES: &[&str] = &["history", "config", "sync_state", "remote_sources", "logs"];
+
+/// 切换存储后端时留下的明文副本，启用保险库时删除
+const PLAINTEXT_LEFTOVERS: &[&str] = &["tokens.json.migrated", "tokens.db.migrated"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VaultStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+    /// 不受保险库保护的明文文件标识
+    pub unencrypted: Vec<String>,
+}
+
+pub struct UnlockedVault {
+    key: [u8; KEY_LEN],
+    kdf: KdfParams,
+}
+
+impl UnlockedVault {
+    /// 以新口令（新 salt）派生密钥，不影响内存中当前的密钥
+    pub fn derive(passphrase: &str) -> AppResult<Self> {
+        let kdf = KdfParams::generate();
+        let key = kdf.derive_key(passphrase)?;
+        Ok(Self { key, kdf })
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> AppResult<VaultFile> {
+        VaultFile::seal(plaintext, &self.key, &self.kdf)
+    }
+}
+
+impl Drop for UnlockedVault {
+    fn drop(&mut self) {
+        self.key.fill(0);
+    }
+}
+
+impl KdfParams {
+    /// 生成使用随机 salt 的默认参数
+    fn generate() -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+
+        Self {
+            algorithm: KDF_ALGORITHM.to_string(),
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+            salt: general_purpose::STANDARD.encode(salt),
+        }
+    }
+
+    /// 从口令派生密钥
+    fn derive_key(&self, passphrase: &str) -> AppResult<[u8; KEY_LEN]> {
+        if self.algorithm != KDF_ALGORITHM {
+            return Err(AppError::new(ErrorCode::Crypto, format!("不支持的密钥派生算法: {}", self.algorithm)));
+        }
+
+        let salt = general_purpose::STANDARD.decode(&self.salt)
+            .map_err(|e| AppError::parse("解析 salt 失败", e))?;
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN))
+            .map_err(|e| AppError::new(ErrorCode::Crypto, "无效的密钥派生参数").with_details(e))?;
+
+        let mut key = [0u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| AppError::new(ErrorCode::Crypto, "派生密钥失败").with_details(e))?;
+
+        Ok(key)
+    }
+}
+
+impl VaultFile {
+    /// 判断文件内容是否为加密格式
+    pub fn parse(content: &str) -> Option<Self> {
+        serde_json::from_str::<VaultFile>(content).ok()
+    }
+
+    fn seal(plaintext: &[u8], key: &[u8; KEY_LEN], kdf: &KdfParams) -> AppResult<Self> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let cipher = XChaCha20Poly1305::new(&Key::from(*key));
+        let ciphertext = cipher.encrypt(&XNonce::from(nonce), plaintext)
+            .map_err(|_| AppError::new(ErrorCode::Crypto, "加密 tokens 失败"))?;
+
+        Ok(Self {
+            vault: VAULT_FORMAT_VERSION,
+            kdf: kdf.clone(),
+            nonce: general_purpose::STANDARD.encode(nonce),
+            ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        })
+    }
+
+    fn open(&self, key: &[u8; KEY_LEN]) -> AppResult<Vec<u8>> {
+        if self.vault != VAULT_FORMAT_VERSION {
+            return Err(AppError::new(ErrorCode::Crypto, format!("不支持的保险库版本: {}", self.vault)));
+        }
+
+        let nonce = general_purpose::STANDARD.decode(&self.nonce)
+            .map_err(|e| AppError::parse("解析 nonce 失败", e))?;
+        let ciphertext = general_purpose::STANDARD.decode(&self.ciphertext)
+            .map_err(|e| AppError::parse("解析密文失败", e))?;
+
+        let nonce: [u8; NONCE_LEN] = nonce.as_slice().try_into()
+            .map_err(|_| AppError::new(ErrorCode::Parse, "无效的 nonce 长度"))?;
+
+        let cipher = XChaCha20Poly1305::new(&Key::from(*key));
+        cipher.decrypt(&XNonce::from(nonce), ciphertext.as_slice())
+            .map_err(|_| AppError::new(ErrorCode::VaultBadPassphrase, "口令错误或文件已被篡改"))
+    }
+}
+
+/// 使用口令解锁加密文件，成功后密钥保留在内存中
+pub fn unlock(file: &VaultFile, passphrase: &str) -> AppResult<Vec<u8>> {
+    let key = file.kdf.derive_key(passphrase)?;
+    let plaintext = file.open(&key)?;
+
+    *UNLOCKED.lock().unwrap() = Some(UnlockedVault { key, kdf: file.kdf.clone() });
+
+    Ok(plaintext)
+}
+
+/// 把密钥设为内存中当前解锁的密钥；调用方需先用它写好 tokens.json，保证内存与磁盘一致
+pub fn install(unlocked: UnlockedVault) {
+    *UNLOCKED.lock().unwrap() = Some(unlocked);
+}
+
+/// 锁定保险库，清除内存中的密钥
+pub fn lock() {
+    *UNLOCKED.lock().unwrap() = None;
+}
+
+pub fn is_unlocked() -> bool {
+    UNLOCKED.lock().unwrap().is_some()
+}
+
+/// 如果已解锁，则加密内容；未解锁时返回 None，由调用方按明文写入
+pub fn seal_if_unlocked(plaintext: &[u8]) -> AppResult<Option<VaultFile>> {
+    match UNLOCKED.lock().unwrap().as_ref() {
+        Some(unlocked) => unlocked.seal(plaintext).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// 使用内存中的密钥解密；未解锁时返回 VAULT_LOCKED 错误
+pub fn open_with_unlocked(file: &VaultFile) -> AppResult<Vec<u8>> {
+    match UNLOCKED.lock().unwrap().as_ref() {
+        Some(unlocked) => file.open(&unlocked.key),
+        None => Err(AppError::vault_locked()),
+    }
+}
+
+/// 用旧口令解密后以当前解锁的密钥重新加密（用于修改口令后迁移备份）
+pub fn reseal(file: &VaultFile, old_passphrase: &str) -> AppResult<VaultFile> {
+    let old_key = file.kdf.derive_key(old_passphrase)?;
+    let plaintext = file.open(&old_key)?;
+
+    seal_if_unlocked(&plaintext)?
+        .ok_or_else(AppError::vault_locked)
+}
+
+/// tokens.json 是否已是保险库格式
+pub fn is_enabled() -> AppResult<bool> {
+    Ok(read_vault_file()?.is_some())
+}
+
+/// 读取当前 tokens 文件中的加密内容（不存在或为明文时返回 None）
+fn read_vault_file() -> AppResult<Option<VaultFile>> {
+    let file_path = crate::token_manager::get_tokens_file_path()?;
+    if !file_path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&file_path)
+        .map_err(|e| AppError::io("读取 tokens.json 失败", e))?;
+
+    Ok(VaultFile::parse(&content))
+}
+
+fn validate_passphrase(passphrase: &str) -> AppResult<()> {
+    if passphrase.trim().is_empty() {
+        return Err(AppError::invalid_input("口令不能为空"));
+    }
+    Ok(())
+}
+
+/// 查询保险库状态，同时列出不受保险库保护的文件
+#[tauri::command]
+pub async fn vault_status() -> AppResult<VaultStatus> {
+    Ok(VaultStatus {
+        enabled: is_enabled()?,
+        unlocked: is_unlocked(),
+        unencrypted: UNENCRYPTED_FILES.iter().map(|file| file.to_string()).collect(),
+    })
+}
+
+/// 启用保险库：用口令加密现有的明文 tokens.json（原地迁移）
+/// 迁移完成后删除明文备份和切换后端留下的副本，并遮蔽历史中的邮箱备注，避免敏感数据残留在磁盘上
+#[tauri::command]
+pub async fn enable_vault(store: State<'_, TokenStore>, passphrase: String) -> AppResult<()> {
+    validate_passphrase(&passphrase)?;
+
+    if is_enabled()? {
+        return Err(AppError::invalid_state("保险库已启用"));
+    }
+
+    if crate::config::load_config()?.storage_backend != crate::config::StorageBackend::Json {
+        return Err(AppError::invalid_state("SQLite 存储不支持保险库加密，请先切换回 JSON 存储"));
+    }
+
+    // 先以明文加载到内存，再用新密钥整体重写为加密格式
+    store.list().await?;
+
+    // Argon2id 派生密钥耗时较长，放到阻塞线程池中执行
+    let key = error::spawn_blocking(move || UnlockedVault::derive(&passphrase)).await?;
+    store.rewrite_with_key(key).await?;
+
+    let file_path = crate::token_manager::get_tokens_file_path()?;
+    for backup in storage::list_backups(&file_path)? {
+        let _ = fs::remove_file(file_path.with_file_name(&backup.file_name));
+    }
+    for leftover in PLAINTEXT_LEFTOVERS {
+        let _ = fs::remove_file(file_path.with_file_name(leftover));
+    }
+
+    crate::token_history::mask_existing()
+}
+
+/// 使用口令解锁保险库
+#[tauri::command]
+pub async fn unlock_vault(store: State<'_, TokenStore>, passphrase: String) -> AppResult<()> {
+    let file = read_vault_file()?
+        .ok_or_else(|| AppError::invalid_state("保险库未启用"))?;
+
+    error::spawn_blocking(move || unlock(&file, &passphrase).map(|_| ())).await?;
+    store.invalidate().await;
+
+    Ok(())
+}
+
+/// 锁定保险库
+#[tauri::command]
+pub async fn lock_vault(store: State<'_, TokenStore>) -> AppResult<()> {
+    lock();
+    store.invalidate().await;
+    Ok(())
+}
+
+/// 修改保险库口令，并用新口令重新加密现有备份
+#[tauri::command]
+pub async fn change_passphrase(
+    store: State<'_, TokenStore>,
+    old_passphrase: String,
+    new_passphrase: String,
+) -> AppResult<()> {
+    validate_passphrase(&new_passphrase)?;
+
+    let file = read_vault_file()?
+        .ok_or_else(|| AppError::invalid_state("保险库未启用"))?;
+
+    // 校验旧口令，并确保内存中已加载解密后的数据
+    let passphrase = old_passphrase.clone();
+    error::spawn_blocking(move || unlock(&file, &passphrase).map(|_| ())).await?;
+    store.invalidate().await;
+    store.list().await?;
+
+    // 先用新密钥写盘，成功后才替换内存中的密钥；失败时内存中仍是与磁盘一致的旧密钥
+    let key = error::spawn_blocking(move || UnlockedVault::derive(&new_passphrase)).await?;
+    store.rewrite_with_key(key).await?;
+
+    // 迁移备份；用更早口令加密的备份无法解密，保持原样。每个备份都要重新派生密钥，同样在阻塞线程池中执行
+    error::spawn_blocking(move || {
+        let file_path = crate::token_manager::get_tokens_file_path()?;
+        for backup in storage::list_backups(&file_path)? {
+            let backup_path = file_path.with_file_name(&backup.file_name);
+            let Some(backup_file) = fs::read_to_string(&backup_path).ok().and_then(|c| VaultFile::parse(&c)) else {
+                continue;
+            };
+
+            if let Ok(resealed) = reseal(&backup_file, &old_passphrase) {
+                let content = serde_json::to_string_pretty(&resealed)
+                    .map_err(|e| AppError::parse("序列化备份失败", e))?;
+                storage::atomic_write(&backup_path, content.as_bytes())?;
+            }
+        }
+        Ok(())
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试使用最低强度的参数，避免默认参数拖慢测试
+    fn cheap_kdf(salt: &[u8]) -> KdfParams {
+        KdfParams {
+            algorithm: KDF_ALGORITHM.to_string(),
+            m_cost: Params::MIN_M_COST,
+            t_cost: Params::MIN_T_COST,
+            p_cost: Params::MIN_P_COST,
+            salt: general_purpose::STANDARD.encode(salt),
+        }
+    }
+
+    #[test]
+    fn seal_and_open_round_trip() {
+        let kdf = cheap_kdf(b"0123456789abcdef");
+        let key = kdf.derive_key("correct horse").unwrap();
+
+        let sealed = VaultFile::seal(b"{\"version\":4}", &key, &kdf).unwrap();
+        let content = serde_json::to_string(&sealed).unwrap();
+        let parsed = VaultFile::parse(&content).unwrap();
+
+        let same_key = parsed.kdf.derive_key("correct horse").unwrap();
+        assert_eq!(parsed.open(&same_key).unwrap(), b"{\"version\":4}");
+    }
+
+    #[test]
+    fn wrong_passphrase_or_tampering_is_rejected() {
+        let kdf = cheap_kdf(b"0123456789abcdef");
+        let key = kdf.derive_key("correct horse").unwrap();
+        let sealed = VaultFile::seal(b"secret", &key, &kdf).unwrap();
+
+        let wrong_key = kdf.derive_key("battery staple").unwrap();
+        assert_eq!(sealed.open(&wrong_key).unwrap_err().code, ErrorCode::VaultBadPassphrase);
+
+        let mut ciphertext = general_purpose::STANDARD.decode(&sealed.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        let tampered = VaultFile { ciphertext: general_purpose::STANDARD.encode(ciphertext), ..sealed.clone() };
+        assert_eq!(tampered.open(&key).unwrap_err().code, ErrorCode::VaultBadPassphrase);
+
+        let future = VaultFile { vault: VAULT_FORMAT_VERSION + 1, ..sealed };
+        assert_eq!(future.open(&key).unwrap_err().code, ErrorCode::Crypto);
+    }
+
+    #[test]
+    fn key_depends_on_salt_and_algorithm() {
+        let first = cheap_kdf(b"0123456789abcdef").derive_key("pass").unwrap();
+        let second = cheap_kdf(b"fedcba9876543210").derive_key("pass").unwrap();
+        assert_ne!(first, second);
+
+        let unsupported = KdfParams { algorithm: "scrypt".to_string(), ..cheap_kdf(b"0123456789abcdef") };
+        assert_eq!(unsupported.derive_key("pass").unwrap_err().code, ErrorCode::Crypto);
+    }
+
+    #[test]
+    fn nonce_is_random_per_seal() {
+        let kdf = cheap_kdf(b"0123456789abcdef");
+        let key = kdf.derive_key("pass").unwrap();
+        let first = VaultFile::seal(b"same", &key, &kdf).unwrap();
+        let second = VaultFile::seal(b"same", &key, &kdf).unwrap();
+        assert_ne!(first.nonce, second.nonce);
+        assert_ne!(first.ciphertext, second.ciphertext);
+    }
+
+    #[test]
+    fn plaintext_store_is_not_a_vault_file() {
+        assert!(VaultFile::parse(r#"{ "version": 4, "tokens": [] }"#).is_none());
+        assert!(VaultFile::parse("[]").is_none());
+    }
+}
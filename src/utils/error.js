@@ -0,0 +1,7 @@
+// 后端命令返回的错误格式为 { code, message, details }
+export function formatError(error) {
+  if (error && typeof error === 'object' && 'message' in error) {
+    return error.details ? `${error.message}: ${error.details}` : error.message
+  }
+  return String(error)
+}
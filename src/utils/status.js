@@ -0,0 +1,14 @@
+// 账号状态的显示文本和标签颜色，与后端 BanStatus 枚举对应
+const BAN_STATUS_DISPLAY = {
+  ACTIVE: { type: 'success', text: '正常' },
+  SUSPENDED: { type: 'warning', text: '已暂停' },
+  BANNED: { type: 'error', text: '已封禁' },
+  SESSION_EXPIRED: { type: 'warning', text: 'Session 失效' },
+  EXHAUSTED: { type: 'warning', text: '积分耗尽' },
+  EXPIRED: { type: 'default', text: '已过期' },
+  UNKNOWN: { type: 'default', text: '未知' }
+}
+
+export function banStatusDisplay(status) {
+  return BAN_STATUS_DISPLAY[status] || BAN_STATUS_DISPLAY.UNKNOWN
+}